@@ -0,0 +1,138 @@
+// The parts of the system API the platform uses, so its logic can run in unit tests outside a canister.
+// Canister builds forward straight to ic-cdk; test builds read a caller and clock the test sets, and keep
+// the certified data and one-way calls where the test can inspect them.
+
+use candid::utils::ArgumentEncoder;
+use candid::Principal;
+use ic_cdk::api::call::RejectionCode;
+use ic_cdk_timers::TimerId;
+use std::time::Duration;
+
+#[cfg(not(test))]
+pub fn caller() -> Principal {
+    ic_cdk::caller()
+}
+
+#[cfg(not(test))]
+pub fn time() -> u64 {
+    ic_cdk::api::time()
+}
+
+#[cfg(not(test))]
+pub fn id() -> Principal {
+    ic_cdk::id()
+}
+
+#[cfg(not(test))]
+pub fn trap(message: &str) -> ! {
+    ic_cdk::trap(message)
+}
+
+#[cfg(not(test))]
+pub fn canister_balance() -> u64 {
+    ic_cdk::api::canister_balance()
+}
+
+#[cfg(not(test))]
+pub fn set_certified_data(data: &[u8]) {
+    ic_cdk::api::set_certified_data(data)
+}
+
+#[cfg(not(test))]
+pub fn data_certificate() -> Option<Vec<u8>> {
+    ic_cdk::api::data_certificate()
+}
+
+#[cfg(not(test))]
+pub fn notify<T: ArgumentEncoder>(canister: Principal, method: &str, args: T) -> Result<(), RejectionCode> {
+    ic_cdk::notify(canister, method, args)
+}
+
+#[cfg(not(test))]
+pub fn set_timer_interval(interval: Duration, func: impl FnMut() + 'static) -> TimerId {
+    ic_cdk_timers::set_timer_interval(interval, func)
+}
+
+#[cfg(not(test))]
+pub fn clear_timer(timer: TimerId) {
+    ic_cdk_timers::clear_timer(timer)
+}
+
+#[cfg(test)]
+pub use mock::*;
+
+#[cfg(test)]
+mod mock {
+    use super::*;
+    use std::cell::RefCell;
+
+    thread_local! {
+        static CALLER: RefCell<Principal> = const { RefCell::new(Principal::anonymous()) };
+        static TIME: RefCell<u64> = const { RefCell::new(1_700_000_000_000_000_000) };
+        static CERTIFIED_DATA: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+        static NOTIFICATIONS: RefCell<Vec<(Principal, String, Vec<u8>)>> = const { RefCell::new(Vec::new()) };
+    }
+
+    // Principal the next calls are made as
+    pub fn set_caller(caller: Principal) {
+        CALLER.with(|current| *current.borrow_mut() = caller);
+    }
+
+    // Move the clock forward
+    pub fn advance_time(nanos: u64) {
+        TIME.with(|time| *time.borrow_mut() += nanos);
+    }
+
+    // Data last passed to `set_certified_data`
+    pub fn certified_data() -> Vec<u8> {
+        CERTIFIED_DATA.with(|data| data.borrow().clone())
+    }
+
+    // One-way calls made so far, as (canister, method, Candid-encoded arguments), oldest first
+    pub fn notifications() -> Vec<(Principal, String, Vec<u8>)> {
+        NOTIFICATIONS.with(|sent| sent.borrow().clone())
+    }
+
+    pub fn caller() -> Principal {
+        CALLER.with(|caller| *caller.borrow())
+    }
+
+    pub fn time() -> u64 {
+        TIME.with(|time| *time.borrow())
+    }
+
+    pub fn id() -> Principal {
+        Principal::from_slice(&[0, 0, 0, 0, 0, 0, 0, 1, 1, 1])
+    }
+
+    // Traps surface as panics, which tests catch with `#[should_panic]` or `std::panic::catch_unwind`
+    pub fn trap(message: &str) -> ! {
+        panic!("{}", message)
+    }
+
+    pub fn canister_balance() -> u64 {
+        0
+    }
+
+    pub fn set_certified_data(data: &[u8]) {
+        CERTIFIED_DATA.with(|current| *current.borrow_mut() = data.to_vec());
+    }
+
+    pub fn data_certificate() -> Option<Vec<u8>> {
+        None
+    }
+
+    // Nothing receives the call; it is kept for `notifications`
+    pub fn notify<T: ArgumentEncoder>(canister: Principal, method: &str, args: T) -> Result<(), RejectionCode> {
+        let encoded = candid::encode_args(args).expect("Failed to encode arguments");
+        NOTIFICATIONS.with(|sent| sent.borrow_mut().push((canister, method.to_string(), encoded)));
+        Ok(())
+    }
+
+    // Timers never fire in tests; tests call the timer functions themselves
+    pub fn set_timer_interval(_interval: Duration, _func: impl FnMut() + 'static) -> TimerId {
+        TimerId::default()
+    }
+
+    pub fn clear_timer(_timer: TimerId) {}
+}
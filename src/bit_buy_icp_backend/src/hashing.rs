@@ -1,10 +1,148 @@
-use candid::{CandidType, Deserialize};  // Correct Candid import
-use ic_cdk_macros::{query, update, init};  // Ensure all necessary macros are imported
-use sha2::{Sha256, Digest};  // For hashing the image data
-use std::collections::HashMap;
-use std::sync::RwLock;
+use candid::{CandidType, Deserialize, Nat, Principal};  // Correct Candid import
+use ic_cdk_macros::{query, update, init, pre_upgrade, post_upgrade};  // Ensure all necessary macros are imported
+use ic_cdk_timers::TimerId;
+use ic_certified_map::{AsHashTree, Hash, RbTree};
+use serde::Serialize;
+use sha2::{Sha256, Sha512, Digest};  // For hashing the image data
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::{DefaultMemoryImpl, Memory as _, StableBTreeMap, Storable};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::time::Duration;
 
-#[derive(CandidType, Deserialize, Clone, Debug)]
+use crate::env;
+use crate::merkle;
+
+// Default upper bound on the size of an uploaded image (5 MiB)
+const MAX_IMAGE_BYTES: u64 = 5 * 1024 * 1024;
+
+// Largest external asset `attest_external_image` will download (64 MiB)
+const MAX_ATTESTED_IMAGE_BYTES: u64 = 64 * 1024 * 1024;
+
+// Upper bound on the size of a listing thumbnail (256 KiB)
+const MAX_THUMBNAIL_BYTES: u64 = 256 * 1024;
+
+// Longest text fields accepted on upload, in bytes; the description limit also applies to edits
+const MAX_PROPERTY_ID_LEN: usize = 128;
+const MAX_DESCRIPTION_LEN: usize = 4096;
+const MAX_TYPE_LABEL_LEN: usize = 64;
+const MAX_KEY_ID_LEN: usize = 256;
+
+// Most items accepted by a single `upload_properties_batch` call
+const MAX_BATCH_LEN: usize = 50;
+
+// Most IDs accepted by a single `get_properties_by_ids` call
+const MAX_LOOKUP_IDS: usize = 200;
+
+// Limits on a single `verify_images_batch` call, so hashing stays within the query instruction limit
+const MAX_VERIFY_BATCH_LEN: usize = 50;
+const MAX_VERIFY_BATCH_BYTES: usize = 2 * 1024 * 1024;
+
+// How long a buyer's reservation holds a listing before it lapses (30 minutes)
+const RESERVATION_TTL_NANOS: u64 = 30 * 60 * 1_000_000_000;
+
+// How long a proposed admin action collects approvals before it lapses (24 hours)
+const PROPOSAL_TTL_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+// ICP ledger on mainnet, used for payments unless the admin configures another ledger
+const DEFAULT_LEDGER_CANISTER: &str = "ryjl3-tyaaa-aaaaa-aaaba-cai";
+
+// Collection metadata reported through the ICRC-7 interface
+const ICRC7_NAME: &str = "BitBuy Properties";
+const ICRC7_SYMBOL: &str = "BITBUY";
+
+// Label in the certified hash tree under which property digests are published
+const CERTIFIED_PROPERTIES_LABEL: &[u8] = b"properties";
+
+// Bounds on chunked uploads; unfinished sessions are dropped after an hour
+const MAX_UPLOAD_CHUNKS: u32 = 1024;
+const UPLOAD_SESSION_TTL_NANOS: u64 = 60 * 60 * 1_000_000_000;
+
+// Bounds on a property's free-form attributes: how many, and their combined key and value bytes
+const MAX_ATTRIBUTES: usize = 32;
+const MAX_ATTRIBUTES_BYTES: usize = 4096;
+
+// Uploads one principal may make per rolling hour, unless the admin configures another limit
+const DEFAULT_UPLOADS_PER_HOUR: u32 = 20;
+const UPLOAD_RATE_WINDOW_NANOS: u64 = 60 * 60 * 1_000_000_000;
+
+// Width of the buckets reported by `uploads_per_day`; day boundaries fall on UTC midnight
+const DAY_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+// How long a completed upload is remembered under its idempotency key, and the longest key accepted
+const IDEMPOTENCY_TTL_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+const MAX_IDEMPOTENCY_KEY_LEN: usize = 64;
+
+// Most listings a single user may keep in their favorites
+const MAX_FAVORITES: usize = 500;
+
+// Most tags a single property may carry
+const MAX_TAGS: usize = 20;
+
+// Ownership of every property is split into this many shares (basis points)
+const TOTAL_SHARES: u64 = 10_000;
+
+// Highest resale royalty a creator can set, in basis points of the sale price (20%)
+const MAX_ROYALTY_BPS: u16 = 2_000;
+
+// Most bids a single auction records, so one bidder can't grow the history without bound
+const MAX_BIDS_PER_AUCTION: usize = 500;
+
+// Longest review comment accepted, in bytes
+const MAX_REVIEW_COMMENT_LEN: usize = 1024;
+
+// Most principals that may be approved to transfer a single property
+const MAX_APPROVALS: usize = 10;
+
+// Longest memo accepted on a transfer
+const MAX_MEMO_LEN: usize = 256;
+
+// Most canisters that can subscribe to audit events
+const MAX_SUBSCRIBERS: usize = 10;
+
+// Limits on collections: the longest name, in bytes, and the most properties one collection may bundle
+const MAX_COLLECTION_NAME_LEN: usize = 128;
+const MAX_COLLECTION_MEMBERS: usize = 50;
+
+// Longest display name a principal may set on its profile, in bytes
+const MAX_DISPLAY_NAME_LEN: usize = 64;
+
+// Most past versions kept per property by `get_property_versions`
+const MAX_PROPERTY_VERSIONS: usize = 10;
+
+// Most principals a private listing can be shared with
+const MAX_ACCESS_GRANTS: usize = 100;
+
+// How often lapsed reservations are released in the background, unless the admin configures another interval
+const DEFAULT_RESERVATION_SWEEP_SECS: u64 = 5 * 60;
+
+// Mean Earth radius used for distance searches, in kilometres
+const EARTH_RADIUS_KM: f64 = 6371.0088;
+
+// Largest page returned by the paginated queries
+const MAX_PAGE_LIMIT: u64 = 100;
+
+// Budget for the properties in one paged response, leaving headroom under the 2 MiB reply limit
+const MAX_RESPONSE_BYTES: usize = 1_500_000;
+
+// Virtual memories handed out by the memory manager: one for the property map, and one for the rest of the
+// state, which `pre_upgrade` writes as its length followed by its CBOR encoding
+const PROPERTIES_MEMORY_ID: MemoryId = MemoryId::new(0);
+const STATE_MEMORY_ID: MemoryId = MemoryId::new(1);
+
+// Size of each body chunk when an image is served over HTTP; larger images are streamed in several chunks
+const HTTP_CHUNK_BYTES: usize = 1024 * 1024;
+
+// Most image bytes moved by one `export_image_chunk` or `import_image_chunk` call, well within the message limits
+const MAX_IMAGE_CHUNK_BYTES: u64 = 1024 * 1024;
+
+// Version of the `Property` layout reported by `canister_info`; bump it whenever fields are added or changed
+const SCHEMA_VERSION: u32 = 4;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 enum PropertyType {
     RealEstate,
     Car,
@@ -12,101 +150,6792 @@ enum PropertyType {
     Other(String), // For any other property categories
 }
 
-#[derive(CandidType, Deserialize, Clone, Debug)]
-struct Property {
-    id: String,
-    property_type: PropertyType,
-    image_hash: String,
-    description: String,
-    owner: String,
+// Where a listing is in the sale process
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+enum ListingStatus {
+    Available,
+    Pending,
+    Sold,
+    Expired, // Passed its `expires_at` without selling
+}
+
+// Who may see a listing; private listings are only shown to their owner and the listed principals
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+enum Visibility {
+    #[default]
+    Public,
+    Private(Vec<Principal>),
+}
+
+// Algorithm used to produce a property's image hashes
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum HashAlgo {
+    #[default]
+    Sha256,
+    Sha512,
+    Blake3,
+}
+
+impl HashAlgo {
+    // Length of this algorithm's digests as hex text
+    fn hex_len(self) -> usize {
+        match self {
+            HashAlgo::Sha256 | HashAlgo::Blake3 => 64,
+            HashAlgo::Sha512 => 128,
+        }
+    }
+}
+
+// Image encodings accepted on upload, recognized from their leading magic bytes
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+enum ImageFormat {
+    Png,
+    Jpeg,
+    Gif,
+    WebP,
+}
+
+// Coordinates of a listing in decimal degrees, with an optional human-readable address
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+struct GeoLocation {
+    lat: f64,
+    lng: f64,
+    address: Option<String>,
+}
+
+impl GeoLocation {
+    fn is_valid(&self) -> bool {
+        (-90.0..=90.0).contains(&self.lat) && (-180.0..=180.0).contains(&self.lng)
+    }
+
+    // Great-circle distance to the given point, using the haversine formula
+    fn distance_km(&self, lat: f64, lng: f64) -> f64 {
+        let (lat1, lat2) = (self.lat.to_radians(), lat.to_radians());
+        let d_lat = lat2 - lat1;
+        let d_lng = (lng - self.lng).to_radians();
+        let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lng / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_KM * a.sqrt().min(1.0).asin()
+    }
+}
+
+// One link in a property's chain of custody
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+struct OwnershipRecord {
+    owner: Principal,
+    acquired_at: u64,
+}
+
+// Record of a single change of ownership, returned to the caller and kept in the property's transfer log
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+struct TransferReceipt {
+    property_id: String,
+    from: Principal,
+    to: Principal,
+    at: u64,
+    memo: Option<String>, // Free-form reference for accounting; sales and auctions leave it empty
+}
+
+// Hold placed on an `Available` listing by a prospective buyer
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+struct Reservation {
+    reserved_by: Principal,
+    expires_at: u64,
+}
+
+// One buyer's rating of a listing
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+struct Review {
+    reviewer: Principal,
+    stars: u8, // 1 to 5
+    comment: String,
+    created_at: u64,
+}
+
+// One bid placed in an auction
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+struct Bid {
+    bidder: Principal,
+    amount_e8s: u64,
+    placed_at: u64,
+}
+
+// An auction running on a listing; each accepted bid beats the previous one, so the last bid is the highest
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+struct Auction {
+    min_bid_e8s: u64,
+    ends_at: u64,
+    bids: Vec<Bid>, // Oldest first
+}
+
+// Missing fields of properties saved before those fields existed are filled in from `Property::default()`
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+struct Property {
+    id: String,
+    property_type: PropertyType,
+    image_hashes: Vec<String>, // The first image is the primary one shown in listings
+    hash_algo: HashAlgo,
+    format: Option<ImageFormat>, // Format of the primary image; unknown for records saved before detection
+    description: String,
+    owner: Principal,
+    created_at: u64, // Nanoseconds since the epoch, from env::time()
+    updated_at: u64,
+    price_e8s: u64, // Asking price in ICP e8s
+    status: ListingStatus,
+    owner_history: Vec<OwnershipRecord>, // Oldest first, starting with the uploader
+    reservation: Option<Reservation>,    // Set while the listing is `Pending` for a buyer
+    attributes: BTreeMap<String, String>, // Type-specific metadata such as square footage or mileage
+    location: Option<GeoLocation>,
+    tags: Vec<String>, // Lowercase and free of duplicates, in the order they were added
+    approvals: Vec<Principal>, // Principals allowed to transfer on the owner's behalf; cleared on every owner change
+    shares: BTreeMap<Principal, u64>, // Basis points held by each co-owner, always summing to TOTAL_SHARES
+    expires_at: Option<u64>, // When set, the listing drops off once this time passes
+    reviews: Vec<Review>,    // Oldest first, at most one per reviewer
+    verified: bool,          // Set by a registered verifier once the listing has been vetted
+    auction: Option<Auction>, // While set, the listing can't be reserved or bought at its fixed price
+    thumbnail_hash: Option<String>, // Small preview for listing grids, stored alongside the full images
+    visibility: Visibility,
+    // Owner's Ed25519 signature over `signed_payload`, and the 32-byte public key it verifies against
+    signature: Option<Vec<u8>>,
+    pubkey: Option<Vec<u8>>,
+    transfers: Vec<TransferReceipt>, // Oldest first
+    featured: bool,                  // Pinned by the admin for promotion on the homepage
+    // Images are client-side ciphertext, hashed and served as-is; the key itself never reaches the canister
+    encrypted: bool,
+    key_id: Option<String>,
+    views: u64, // Bumped through `record_view`, since `get_property_by_id` is a query and can't keep count itself
+    creator: Principal, // The original uploader, who keeps earning royalties after the property changes hands
+    royalty_bps: u16,   // Cut of every resale paid to `creator`, in basis points of the price
+    image_size_bytes: u64, // Uncompressed length of all of `image_hashes` together; the thumbnail isn't counted
+}
+
+// One field that differs between two versions of a property, rendered as text
+#[derive(CandidType, Deserialize, Clone, Debug)]
+struct FieldChange {
+    field: String,
+    old: String,
+    new: String,
+}
+
+// Helper function to give a new property's full set of shares to its uploader
+fn sole_shareholder(owner: Principal) -> BTreeMap<Principal, u64> {
+    BTreeMap::from([(owner, TOTAL_SHARES)])
+}
+
+impl Property {
+    // Whether the listing shows up in queries made by `caller`
+    fn is_visible_to(&self, caller: Principal) -> bool {
+        match &self.visibility {
+            Visibility::Public => true,
+            Visibility::Private(allowed) => self.owner == caller || allowed.contains(&caller),
+        }
+    }
+
+    // When the property last changed owners; the upload that started its history doesn't count
+    fn last_transfer_at(&self) -> Option<u64> {
+        self.owner_history.iter().skip(1).last().map(|record| record.acquired_at)
+    }
+
+    // Hashes of every stored image this property references, including its thumbnail
+    fn stored_hashes(&self) -> impl Iterator<Item = &String> {
+        self.image_hashes.iter().chain(self.thumbnail_hash.iter())
+    }
+
+    // Whether an unsold listing is past its expiry but hasn't been marked `Expired` yet
+    fn has_expired(&self, now: u64) -> bool {
+        let unsold = matches!(self.status, ListingStatus::Available | ListingStatus::Pending);
+        unsold && self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    // Copy of the property as readers should see it, with expiry and lapsed holds applied lazily until a sweep
+    // stores them. A hold whose payment is still in flight stays until the sale settles, as in the sweep.
+    fn view(&self, now: u64) -> Property {
+        let mut property = self.clone();
+        if property.has_expired(now) {
+            property.status = ListingStatus::Expired;
+            property.reservation = None;
+        } else if !sale_in_flight(&property.id, now) {
+            property.expire_reservation(now);
+        }
+        property
+    }
+
+    // The view listing queries return, or `None` once the listing has expired and dropped off them
+    fn listed_view(&self, now: u64) -> Option<Property> {
+        let property = self.view(now);
+        (property.status != ListingStatus::Expired).then_some(property)
+    }
+
+    // A lapsed reservation frees the listing again; returns whether one was released
+    fn expire_reservation(&mut self, now: u64) -> bool {
+        match &self.reservation {
+            Some(reservation) if reservation.expires_at <= now => {
+                self.reservation = None;
+                self.status = ListingStatus::Available;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Default for Property {
+    fn default() -> Self {
+        Property {
+            id: String::new(),
+            property_type: PropertyType::Other(String::new()),
+            image_hashes: Vec::new(),
+            hash_algo: HashAlgo::Sha256,
+            format: None,
+            description: String::new(),
+            owner: Principal::anonymous(),
+            created_at: 0,
+            updated_at: 0,
+            price_e8s: 0,
+            status: ListingStatus::Available,
+            owner_history: Vec::new(),
+            reservation: None,
+            attributes: BTreeMap::new(),
+            location: None,
+            tags: Vec::new(),
+            approvals: Vec::new(),
+            shares: BTreeMap::new(),
+            expires_at: None,
+            reviews: Vec::new(),
+            verified: false,
+            auction: None,
+            thumbnail_hash: None,
+            visibility: Visibility::Public,
+            signature: None,
+            pubkey: None,
+            transfers: Vec::new(),
+            featured: false,
+            encrypted: false,
+            key_id: None,
+            views: 0,
+            creator: Principal::anonymous(),
+            royalty_bps: 0,
+            // Filled in from the stored images by `backfill_image_sizes`
+            image_size_bytes: 0,
+        }
+    }
+}
+
+// A property together with the data a client needs to check it against the certified state
+#[derive(CandidType, Deserialize, Clone, Debug)]
+struct CertifiedProperty {
+    property: Property,
+    certificate: Option<Vec<u8>>, // Only available in non-replicated query calls
+    witness: Vec<u8>,             // CBOR-encoded hash tree covering this property's digest
+}
+
+// Orderings offered by `get_properties_sorted`
+#[derive(CandidType, Deserialize, Clone, Copy, Debug)]
+enum SortKey {
+    CreatedAt,
+    Price,
+    Id,
+}
+
+// One page of properties plus the total number available
+#[derive(CandidType, Deserialize, Clone, Debug)]
+struct PropertyPage {
+    entries: Vec<Property>,
+    total: u64,
+    truncated: bool, // Fewer entries than requested were returned to keep the response small enough
+}
+
+// Properties following a cursor, plus the cursor to pass for the next page (`None` once the end is reached)
+#[derive(CandidType, Deserialize, Clone, Debug)]
+struct CursorPage {
+    entries: Vec<Property>,
+    next_cursor: Option<String>,
+}
+
+// Arguments of a single `upload_property` call, bundled for batch uploads
+#[derive(CandidType, Deserialize, Clone, Debug)]
+struct UploadRequest {
+    property_id: String,
+    property_type: PropertyType,
+    images: Vec<Vec<u8>>,
+    description: String,
+    price_e8s: u64,
+    hash_algo: Option<HashAlgo>, // Defaults to SHA-256
+    options: Option<UploadOptions>,
+}
+
+// Optional extras accepted on upload; every field may be left out
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+struct UploadOptions {
+    attributes: Option<Vec<(String, String)>>,
+    location: Option<GeoLocation>,
+    tags: Option<Vec<String>>,
+    expires_at: Option<u64>,
+    thumbnail: Option<Vec<u8>>,
+    visibility: Option<Visibility>, // Defaults to `Public`
+    // The images were encrypted by the client, so their format isn't checked; `key_id` names the key to decrypt with
+    encrypted: Option<bool>,
+    key_id: Option<String>,
+    // Retrying with the same key returns the first call's result instead of uploading again
+    idempotency_key: Option<String>,
+    // Store the image bytes LZ4-compressed when that makes them smaller; reads still return the original bytes
+    compress: Option<bool>,
+    // Resale royalty for the uploader, in basis points up to MAX_ROYALTY_BPS; defaults to none
+    royalty_bps: Option<u16>,
+}
+
+// A property's primary image as stored; encrypted images come back as the uploaded ciphertext
+#[derive(CandidType, Deserialize, Clone, Debug)]
+struct ImageContent {
+    image_data: Vec<u8>,
+    encrypted: bool,
+    key_id: Option<String>, // The client's reference to the decryption key, if one was given
+}
+
+// Successful upload: the stored property and its image hashes, plus any existing properties already listing the same image
+#[derive(CandidType, Deserialize, Clone, Debug)]
+struct UploadResult {
+    hashes: Vec<String>,
+    duplicate_of: Vec<String>,
+    property: Property, // As stored, so callers can render it without a follow-up query
+}
+
+// Kinds of mutation recorded in the audit log
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+enum AuditEventKind {
+    Uploaded,
+    Deleted,
+    Restored,
+    Purged,
+    Transferred { to: Principal },
+    DescriptionUpdated,
+    ImageAdded { hash: String },
+    ImageRemoved { hash: String },
+    AttributeSet { key: String },
+    AttributeRemoved { key: String },
+    LocationUpdated,
+    TagAdded { tag: String },
+    TagRemoved { tag: String },
+    Approved { spender: Principal },
+    ApprovalRevoked { spender: Principal },
+    SharesTransferred { to: Principal, amount: u64 },
+    ExpiryUpdated { expires_at: Option<u64> },
+    ReviewAdded { stars: u8 },
+    VerificationChanged { verified: bool },
+    FeaturedChanged { featured: bool },
+    VisibilityChanged { public: bool },
+    AccessGranted { grantee: Principal },
+    AccessRevoked { grantee: Principal },
+    MetadataSigned,
+    AuctionStarted { min_bid_e8s: u64, ends_at: u64 },
+    BidPlaced { amount_e8s: u64 },
+    AuctionSettled { winner: Option<Principal> },
+    StatusChanged { status: ListingStatus },
+    Reserved { expires_at: u64 },
+    ReservationCancelled,
+    ReservationExpired,
+    SwapApproved { for_property: String },
+    PlatformReset, // Recorded with an empty property ID
+    StateImported, // Recorded with an empty property ID, after the imported events
+    OrphanRemoved, // Removed by the admin because its image bytes were missing
+    Rehashed { algo: HashAlgo },
+    RolledBack { to: u64 }, // Put back to its state as of `to` by the admin
+}
+
+// Entry in the append-only audit log
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+struct AuditEvent {
+    id: u64, // Increases with every event and is never reused, even after old events are purged
+    kind: AuditEventKind,
+    property_id: String,
+    caller: Principal,
+    timestamp: u64,
+}
+
+// Properties a seller lists together as one package, such as a car and its spare parts
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+struct Collection {
+    id: String,
+    name: String,
+    owner: Principal,
+    member_ids: Vec<String>, // In the order they were added; all owned by `owner` when added
+}
+
+// A collection with its members resolved to the properties the caller can see
+#[derive(CandidType, Deserialize, Clone, Debug)]
+struct CollectionView {
+    id: String,
+    name: String,
+    owner: Principal,
+    members: Vec<Property>,
+}
+
+// Human-readable details a principal publishes about itself
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+struct Profile {
+    display_name: String,
+    avatar_hash: Option<String>, // Hex digest of an avatar image the client stores elsewhere
+}
+
+// Kinds of ICRC-3 style transactions: a property entering the live listings, changing owner, or leaving them
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+enum TransactionKind {
+    Mint,
+    Transfer,
+    Burn,
+}
+
+// Entry in the transaction log, the ownership-changing subset of the audit log in a shape block explorers expect
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+struct Transaction {
+    index: u64, // Position in the log, starting at zero
+    kind: TransactionKind,
+    property_id: String,
+    from: Option<Principal>, // Unset for mints
+    to: Option<Principal>,   // Unset for burns
+    caller: Principal,
+    timestamp: u64,
+}
+
+// Canister method called with every new `AuditEvent`
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+struct Subscriber {
+    canister: Principal,
+    method: String,
+}
+
+// Request and response shapes of the IC HTTP gateway interface
+#[derive(CandidType, Deserialize, Clone, Debug)]
+struct HttpRequest {
+    method: String,
+    url: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+struct HttpResponse {
+    status_code: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    streaming_strategy: Option<StreamingStrategy>, // Set when the body continues in further chunks
+}
+
+// Tells the gateway where to fetch the rest of a streamed body
+#[derive(CandidType, Deserialize, Clone, Debug)]
+enum StreamingStrategy {
+    Callback {
+        callback: StreamingCallback,
+        token: StreamingCallbackToken,
+    },
+}
+
+candid::define_function!(StreamingCallback : (StreamingCallbackToken) -> (StreamingCallbackHttpResponse) query);
+
+// Identifies the next chunk of a streamed image
+#[derive(CandidType, Deserialize, Clone, Debug)]
+struct StreamingCallbackToken {
+    property_id: String,
+    image_hash: String,
+    index: u64,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+struct StreamingCallbackHttpResponse {
+    body: Vec<u8>,
+    token: Option<StreamingCallbackToken>, // `None` once the last chunk has been sent
+}
+
+// Errors returned by every endpoint that can fail instead of trapping, so clients match on a single type
+#[derive(CandidType, Deserialize, Clone, Debug)]
+enum PlatformError {
+    NotFound, // The property, or the session, image, tag, attribute, or collection named in the call
+    Unauthorized,
+    Paused,
+    EmptyImage,
+    MissingAttribute { key: String }, // The admin requires this attribute for the property type
+    DuplicateId { id: String },
+    DuplicateImage { existing_id: String }, // Only while the admin blocks duplicate images
+    HashCollision { hash: String },         // Different bytes are already stored under this image hash
+    TooLarge { limit_bytes: u64 },          // The image limit for this property type, or the thumbnail limit
+    Overflow,                               // A price, bid, or share amount would go past what a u64 can hold
+    RateLimited { retry_after_secs: u64 },
+    CooldownActive { retry_after_secs: u64 }, // The property changed owners too recently to be transferred again
+    QuotaExceeded { limit: u64 }, // The caller, recipient, or buyer already holds the most properties an owner may have
+    PlatformFull { limit: u64 },  // The platform already holds the most live properties the admin allows
+    LimitReached(String),         // Names the limit that was hit, such as the most tags a property may have
+    NotAvailable,                 // The listing is reserved, sold, expired, or being auctioned
+    BidTooLow { minimum_e8s: u64 },
+    LedgerRejected { error: TransferFromError },
+    CallFailed { message: String }, // Another canister rejected the call
+    // The payment went through but the listing changed hands or was withdrawn meanwhile; the buyer is refunded
+    SaleInterrupted,
+    Conflict(String),     // The call doesn't fit the current state, e.g. an auction that has already ended
+    InvalidInput(String), // Says which argument was rejected and why
+}
+
+// ICRC-1 account and the ICRC-1 `icrc1_transfer` and ICRC-2 `icrc2_transfer_from` types used to pay through the ledger
+#[derive(CandidType, Deserialize, Clone, Debug)]
+struct Account {
+    owner: Principal,
+    subaccount: Option<Vec<u8>>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+struct TransferFromArgs {
+    spender_subaccount: Option<Vec<u8>>,
+    from: Account,
+    to: Account,
+    amount: Nat,
+    fee: Option<Nat>,
+    memo: Option<Vec<u8>>,
+    created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+struct TransferArgs {
+    from_subaccount: Option<Vec<u8>>,
+    to: Account,
+    amount: Nat,
+    fee: Option<Nat>,
+    memo: Option<Vec<u8>>,
+    created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+enum TransferFromError {
+    BadFee { expected_fee: Nat },
+    BadBurn { min_burn_amount: Nat },
+    InsufficientFunds { balance: Nat },
+    InsufficientAllowance { allowance: Nat },
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: Nat },
+    TemporarilyUnavailable,
+    GenericError { error_code: Nat, message: String },
+}
+
+// Request and response shapes of the asset canister's `get` and `get_chunk` methods
+#[derive(CandidType, Deserialize, Clone, Debug)]
+struct AssetGetArgs {
+    key: String,
+    accept_encodings: Vec<String>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+struct AssetEncodedContent {
+    content: Vec<u8>,
+    content_type: String,
+    content_encoding: String,
+    total_length: Nat,
+    sha256: Option<Vec<u8>>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+struct AssetGetChunkArgs {
+    key: String,
+    content_encoding: String,
+    index: Nat,
+    sha256: Option<Vec<u8>>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+struct AssetChunk {
+    content: Vec<u8>,
+}
+
+// Platform-wide figures for dashboards, gathered in one call
+#[derive(CandidType, Deserialize, Clone, Debug)]
+struct PlatformStats {
+    total_properties: u64,
+    per_type_counts: Vec<(String, u64)>, // Sorted by type label
+    unique_owners: u64,
+    total_image_bytes: u64, // Bytes actually stored, so shared images count once
+    latest_upload_at: Option<u64>,
+}
+
+// Resource figures for monitoring, so operators can top up cycles before the canister freezes
+#[derive(CandidType, Deserialize, Clone, Debug)]
+struct CanisterHealth {
+    cycles_balance: u64,
+    heap_bytes: u64, // Size of the wasm heap, which only ever grows
+    stable_memory_bytes: u64,
+    property_count: u64,
+    stored_image_bytes: u64,
+}
+
+// Destructive admin actions; once the approval threshold is above one they only run through `propose_action`
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+enum AdminAction {
+    ResetPlatform,
+    ImportState { force: bool }, // Loads the snapshot staged with `import_chunk` when the action runs
+    SetAdminCouncil { admins: Vec<Principal>, threshold: u32 }, // Admins besides the primary one
+    RollbackTo { timestamp: u64 },
+    DeletePropertiesByOwner { owner: Principal },
+    DeletePropertiesByType { property_type: PropertyType },
+    RemoveOrphaned,
+    CleanUpAnonymousOwned { new_owner: Option<Principal> },
+    RehashAll { new_algo: HashAlgo },
+    PurgeEventsBefore { timestamp: u64 },
+    PruneDeletions { before: u64 },
+    SetLedgerCanister { ledger: Principal }, // Guarded like the destructive actions, since it redirects payments
+}
+
+impl AdminAction {
+    // Name of the endpoint the action stands in for, shown in pending proposals instead of the full arguments
+    fn name(&self) -> &'static str {
+        match self {
+            AdminAction::ResetPlatform => "reset_platform",
+            AdminAction::ImportState { .. } => "import_state",
+            AdminAction::SetAdminCouncil { .. } => "set_admin_council",
+            AdminAction::RollbackTo { .. } => "rollback_to",
+            AdminAction::DeletePropertiesByOwner { .. } => "delete_properties_by_owner",
+            AdminAction::DeletePropertiesByType { .. } => "delete_properties_by_type",
+            AdminAction::RemoveOrphaned => "remove_orphaned",
+            AdminAction::CleanUpAnonymousOwned { .. } => "clean_up_anonymous_owned",
+            AdminAction::RehashAll { .. } => "rehash_all",
+            AdminAction::PurgeEventsBefore { .. } => "purge_events_before",
+            AdminAction::PruneDeletions { .. } => "prune_deletions",
+            AdminAction::SetLedgerCanister { .. } => "set_ledger_canister",
+        }
+    }
+}
+
+// An admin action waiting for approvals; the proposer's approval counts
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+struct Proposal {
+    id: u64,
+    action: AdminAction,
+    proposer: Principal,
+    approvals: Vec<Principal>, // Distinct admins, in the order they approved
+    created_at: u64,
+    expires_at: u64,
+}
+
+// A pending proposal as listed by `get_pending_proposals`, without the action's arguments
+#[derive(CandidType, Deserialize, Clone, Debug)]
+struct PendingProposal {
+    id: u64,
+    action: String,
+    proposer: Principal,
+    approvals: Vec<Principal>,
+    threshold: u32,
+    created_at: u64,
+    expires_at: u64,
+}
+
+// Identity of the canister and the record layout it serves, so clients can detect incompatible upgrades
+#[derive(CandidType, Deserialize, Clone, Debug)]
+struct CanisterInfo {
+    canister_id: Principal,
+    schema_version: u32,
+    property_count: u64,
+}
+
+// Copy of the platform's data, used to back it up or move it to a fresh canister. `export_state` hands it out
+// in pages and `import_chunk` takes it back the same way; a full snapshot is all pages' entries together.
+// Image bytes travel separately, through `export_image_chunk` and `import_image_chunk`, since a single image
+// can be larger than a message.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+struct PlatformSnapshot {
+    properties: Vec<Property>,
+    trash: Vec<Property>,
+    images: Vec<(String, u64)>, // Hash and length in bytes of every stored image
+    events: Vec<AuditEvent>,
+    owed_payouts: Vec<(Principal, u64)>,
+}
+
+impl PlatformSnapshot {
+    // Add another page's entries after this one's
+    fn append(&mut self, page: PlatformSnapshot) {
+        self.properties.extend(page.properties);
+        self.trash.extend(page.trash);
+        self.images.extend(page.images);
+        self.events.extend(page.events);
+        self.owed_payouts.extend(page.owed_payouts);
+    }
+}
+
+// A snapshot staged for `import_state`: the pages sent so far and the image bytes received, keyed by hash
+#[derive(Default)]
+struct StagedImport {
+    snapshot: PlatformSnapshot,
+    images: HashMap<String, Vec<u8>>,
+}
+
+// One page of `export_state`, plus the offset to pass for the next page (`None` once the end is reached)
+#[derive(CandidType, Deserialize, Clone, Debug)]
+struct SnapshotPage {
+    snapshot: PlatformSnapshot,
+    next_offset: Option<u64>,
+}
+
+// An entry `export_state` pages through; live properties come first, then trashed ones, images, events, and
+// owed payouts
+enum SnapshotEntry<'a> {
+    Property(Box<Property>),
+    Trashed(&'a Property),
+    Image(&'a String),
+    Event(&'a AuditEvent),
+    Payout(Principal, u64),
+}
+
+// An image being uploaded in chunks; kept on the heap only, so upgrades drop open sessions
+struct UploadSession {
+    uploader: Principal,
+    property_id: String,
+    total_chunks: u32,
+    chunks: BTreeMap<u32, Vec<u8>>,
+    started_at: u64,
+}
+
+// Image bytes are saved as CBOR byte strings rather than arrays of numbers, which would double their size
+mod stored_images {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use serde_bytes::{ByteBuf, Bytes};
+    use std::collections::HashMap;
+
+    // Image bytes and reference count, keyed by hash
+    type Images = HashMap<String, (Vec<u8>, u32)>;
+
+    pub fn serialize<S: Serializer>(images: &Images, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(images.iter().map(|(hash, (image_data, refs))| (hash, (Bytes::new(image_data), refs))))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Images, D::Error> {
+        let images: HashMap<String, (ByteBuf, u32)> = HashMap::deserialize(deserializer)?;
+        Ok(images.into_iter().map(|(hash, (image_data, refs))| (hash, (image_data.into_vec(), refs))).collect())
+    }
+}
+
+// Live properties, kept in stable memory so they don't have to fit in the heap or be copied around upgrades.
+// Reads hand out owned copies; `get_mut` hands out a guard whose edits are saved when it's committed.
+struct PropertyStore {
+    map: StableBTreeMap<String, Property, Memory>,
+}
+
+impl Default for PropertyStore {
+    // Opens the map already in stable memory, so the properties saved before an upgrade come back with it
+    fn default() -> Self {
+        PropertyStore {
+            map: StableBTreeMap::init(memory(PROPERTIES_MEMORY_ID)),
+        }
+    }
+}
+
+impl PropertyStore {
+    fn get(&self, id: &str) -> Option<Property> {
+        self.map.get(&id.to_string())
+    }
+
+    fn get_mut(&mut self, id: &str) -> Option<PropertyMut<'_>> {
+        let property = self.map.get(&id.to_string())?;
+        Some(PropertyMut {
+            map: &mut self.map,
+            id: id.to_string(),
+            property,
+            dirty: false,
+        })
+    }
+
+    fn contains_key(&self, id: &str) -> bool {
+        self.map.contains_key(&id.to_string())
+    }
+
+    fn len(&self) -> usize {
+        self.map.len() as usize
+    }
+
+    fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    fn insert(&mut self, id: String, property: Property) -> Option<Property> {
+        self.map.insert(id, property)
+    }
+
+    fn remove(&mut self, id: &str) -> Option<Property> {
+        self.map.remove(&id.to_string())
+    }
+
+    fn clear(&mut self) {
+        self.map.clear_new();
+    }
+
+    // Entries come out in ID order
+    fn iter(&self) -> impl Iterator<Item = (String, Property)> + '_ {
+        self.map.iter()
+    }
+
+    fn keys(&self) -> impl Iterator<Item = String> + '_ {
+        self.map.iter().map(|(id, _)| id)
+    }
+
+    fn values(&self) -> impl Iterator<Item = Property> + '_ {
+        self.map.iter().map(|(_, property)| property)
+    }
+}
+
+// A property borrowed out of the store for editing; changes are only written back by `commit`
+struct PropertyMut<'a> {
+    map: &'a mut StableBTreeMap<String, Property, Memory>,
+    id: String,
+    property: Property,
+    dirty: bool,
+}
+
+impl std::ops::Deref for PropertyMut<'_> {
+    type Target = Property;
+
+    fn deref(&self) -> &Property {
+        &self.property
+    }
+}
+
+impl std::ops::DerefMut for PropertyMut<'_> {
+    fn deref_mut(&mut self) -> &mut Property {
+        self.dirty = true;
+        &mut self.property
+    }
+}
+
+impl PropertyMut<'_> {
+    // Save the edits; a guard dropped without this leaves the stored property as it was, so an endpoint that
+    // bails out with an error part-way through an edit doesn't persist half of it
+    fn commit(self) {
+        if self.dirty {
+            self.map.insert(self.id, self.property);
+        }
+    }
+}
+
+// Properties are stored in stable memory as CBOR, which fills in fields added since they were written
+impl Storable for Property {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(self, &mut bytes).expect("Failed to encode property");
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        ciborium::from_reader(bytes.as_ref()).expect("Failed to decode property")
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Saved as CBOR around upgrades. Fields missing from older saved state take their default, so new fields
+// can be added without keeping the previous layout around.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+struct DecentralizedPlatform {
+    // Already in stable memory, so left out of the state saved by `pre_upgrade`
+    #[serde(skip)]
+    properties: PropertyStore,
+    // Soft-deleted properties, hidden from queries until restored or purged
+    trash: HashMap<String, Property>,
+    // Raw image bytes keyed by their hash, so identical images are stored once, along with how many
+    // references from live or trashed properties point at them; bytes are freed when the count reaches zero
+    #[serde(with = "stored_images")]
+    images: HashMap<String, (Vec<u8>, u32)>,
+    // Hashes of the images in `images` whose bytes are stored LZ4-compressed
+    compressed_images: HashSet<String>,
+    // Append-only log of mutations, oldest first
+    events: Vec<AuditEvent>,
+    // ID given to the next audit event
+    next_event_id: u64,
+    // Overrides MAX_IMAGE_BYTES when set by the admin
+    max_image_bytes: Option<u64>,
+    // Per-type image size limits keyed by `type_label`, taking precedence over `max_image_bytes`
+    type_image_limits: HashMap<String, u64>,
+    // Attribute keys every new upload of a property type must set, keyed by the `type_label` of the type
+    required_attributes: HashMap<String, Vec<String>>,
+    // Description templates the frontend can prefill for new listings, keyed by `type_label`
+    type_templates: HashMap<String, String>,
+    // Overrides DEFAULT_LEDGER_CANISTER when set by the admin, e.g. for a local ledger
+    ledger_canister: Option<Principal>,
+    // Overrides DEFAULT_UPLOADS_PER_HOUR when set by the admin
+    max_uploads_per_hour: Option<u32>,
+    // Overrides DEFAULT_RESERVATION_SWEEP_SECS when set by the admin
+    reservation_sweep_secs: Option<u64>,
+    // Principals trusted by the admin to vet listings
+    verifiers: HashSet<Principal>,
+    // Property IDs each user has bookmarked; entries for trashed properties are kept until purged
+    favorites: HashMap<Principal, HashSet<String>>,
+    // Labels of the property types uploads may use, as given by `type_label`; `None` allows every type
+    allowed_types: Option<HashSet<String>>,
+    // Canisters notified of every audit event, registered by the admin
+    subscribers: Vec<Subscriber>,
+    // The most recent states of each property, oldest first, with the current state last
+    versions: HashMap<String, Vec<Property>>,
+    // When each entry of `versions` was recorded, aligned from the newest end; versions kept from before
+    // this was tracked have no time
+    version_times: HashMap<String, Vec<u64>>,
+    // Maintenance mode: while set, every non-admin update is rejected
+    paused: bool,
+    // When set, uploads reusing an image already listed by a live property are refused instead of reported
+    block_duplicate_images: bool,
+    // Deployment-specific bytes prepended to every image before hashing; `None` hashes the bare image
+    hash_salt: Option<Vec<u8>>,
+    // Pending swap offers: property ID -> the property its owner agreed to swap it for, and who agreed
+    swap_approvals: HashMap<String, (String, Principal)>,
+    // Principals allowed to upload, managed by the admin; `None` lets every signed-in caller upload
+    seller_allowlist: Option<HashSet<Principal>>,
+    // Most live properties one principal may hold, set by the admin; `None` means no limit
+    max_properties_per_owner: Option<u64>,
+    // Most live properties the whole platform may hold, set by the admin to bound memory; `None` means no limit
+    max_total_properties: Option<u64>,
+    // Minimum time between two changes of owner through `transfer_property`, set by the admin; `None` means none
+    transfer_cooldown_secs: Option<u64>,
+    // Tombstones of properties taken off the live listings, as (property ID, deleted at), oldest first
+    deletions: Vec<(String, u64)>,
+    // Seller-defined bundles of live properties, keyed by collection ID
+    collections: HashMap<String, Collection>,
+    // Lowercase words or phrases that listings may not contain, managed by the admin
+    blocked_keywords: HashSet<String>,
+    // Append-only log of mints, transfers, and burns, derived from the audit events
+    transactions: Vec<Transaction>,
+    // Self-published profiles, each set only by the principal it describes
+    profiles: HashMap<Principal, Profile>,
+    // Admins besides the primary one who can propose and approve destructive actions
+    admin_council: HashSet<Principal>,
+    // Distinct admin approvals a destructive action needs; 0 or 1 lets the primary admin act alone
+    approval_threshold: u32,
+    // Admin actions still collecting approvals, keyed by proposal ID, plus the ID the next proposal gets
+    proposals: BTreeMap<u64, Proposal>,
+    next_proposal_id: u64,
+    // Sale proceeds and refunds held for principals whose payout from escrow didn't go through, in e8s
+    owed_payouts: HashMap<Principal, u64>,
+}
+
+impl DecentralizedPlatform {
+    // Append an audit event attributed to the current caller
+    fn record_event(&mut self, kind: AuditEventKind, property_id: &str) {
+        let event = AuditEvent {
+            id: self.next_event_id,
+            kind,
+            property_id: property_id.to_string(),
+            caller: env::caller(),
+            timestamp: env::time(),
+        };
+        self.next_event_id += 1;
+        // One-way calls, so a slow or failing subscriber can't hold up or undo the mutation
+        for subscriber in &self.subscribers {
+            let _ = env::notify(subscriber.canister, &subscriber.method, (event.clone(),));
+        }
+        // The owner index still holds the owner from before this mutation, so comparing it with the stored
+        // record shows whether the property was minted, transferred, or burned
+        let previous_owner = OWNER_INDEX.with(|index| index.borrow().indexed_owner.get(property_id).copied());
+        let current_owner = self.properties.get(property_id).map(|property| property.owner);
+        let transaction_kind = match (previous_owner, current_owner) {
+            (None, Some(_)) => Some(TransactionKind::Mint),
+            (Some(from), Some(to)) if from != to => Some(TransactionKind::Transfer),
+            (Some(_), None) => Some(TransactionKind::Burn),
+            _ => None,
+        };
+        if let Some(kind) = transaction_kind {
+            self.transactions.push(Transaction {
+                index: self.transactions.len() as u64,
+                kind,
+                property_id: property_id.to_string(),
+                from: previous_owner,
+                to: current_owner,
+                caller: event.caller,
+                timestamp: event.timestamp,
+            });
+        }
+        let recorded_at = event.timestamp;
+        self.events.push(event);
+        // Every mutation records an event afterwards, so this also keeps the certified data, owner index,
+        // and version history current
+        certify_property(self, property_id);
+        index_owner(self, property_id);
+        if let Some(property) = self.properties.get(property_id) {
+            let versions = self.versions.entry(property_id.to_string()).or_default();
+            versions.push(property.clone());
+            if versions.len() > MAX_PROPERTY_VERSIONS {
+                versions.remove(0);
+            }
+            let times = self.version_times.entry(property_id.to_string()).or_default();
+            times.push(recorded_at);
+            if times.len() > MAX_PROPERTY_VERSIONS {
+                times.remove(0);
+            }
+        }
+        #[cfg(test)]
+        check_owner_index(self);
+    }
+
+    // The latest version of a property recorded at or before `timestamp`, with when it was recorded
+    fn version_as_of(&self, property_id: &str, timestamp: u64) -> Option<(u64, &Property)> {
+        let versions = self.versions.get(property_id)?;
+        let times = self.version_times.get(property_id)?;
+        versions
+            .iter()
+            .rev()
+            .zip(times.iter().rev())
+            .find(|(_, recorded_at)| **recorded_at <= timestamp)
+            .map(|(property, recorded_at)| (*recorded_at, property))
+    }
+
+    fn max_image_bytes(&self) -> u64 {
+        self.max_image_bytes.unwrap_or(MAX_IMAGE_BYTES)
+    }
+
+    fn max_image_bytes_for(&self, property_type: &PropertyType) -> u64 {
+        self.type_image_limits
+            .get(&type_label(property_type))
+            .copied()
+            .unwrap_or_else(|| self.max_image_bytes())
+    }
+
+    // Largest image any property type may have, e.g. for chunked uploads whose type isn't known yet
+    fn largest_image_limit(&self) -> u64 {
+        self.type_image_limits.values().copied().fold(self.max_image_bytes(), u64::max)
+    }
+
+    fn hash_salt(&self) -> &[u8] {
+        self.hash_salt.as_deref().unwrap_or_default()
+    }
+
+    fn max_uploads_per_hour(&self) -> u32 {
+        self.max_uploads_per_hour.unwrap_or(DEFAULT_UPLOADS_PER_HOUR)
+    }
+
+    fn reservation_sweep_secs(&self) -> u64 {
+        self.reservation_sweep_secs.unwrap_or(DEFAULT_RESERVATION_SWEEP_SECS)
+    }
+
+    fn allows_type(&self, property_type: &PropertyType) -> bool {
+        self.allowed_types
+            .as_ref()
+            .is_none_or(|allowed| allowed.contains(&type_label(property_type)))
+    }
+
+    fn is_seller(&self, principal: Principal) -> bool {
+        self.seller_allowlist
+            .as_ref()
+            .is_none_or(|allowlist| allowlist.contains(&principal))
+    }
+
+    // Whether the text contains a blocked keyword as whole words, ignoring case and punctuation
+    fn contains_blocked_keyword(&self, text: &str) -> bool {
+        if self.blocked_keywords.is_empty() {
+            return false;
+        }
+        let words = keyword_words(text);
+        self.blocked_keywords.iter().any(|keyword| {
+            let keyword = keyword_words(keyword);
+            !keyword.is_empty() && words.windows(keyword.len()).any(|window| window == keyword.as_slice())
+        })
+    }
+
+    fn ledger_canister(&self) -> Principal {
+        self.ledger_canister
+            .unwrap_or_else(|| Principal::from_text(DEFAULT_LEDGER_CANISTER).expect("Valid ledger canister ID"))
+    }
+
+    // Take a reference to an image, storing its bytes if no property referenced it yet.
+    // Images are keyed by their full hex hash, and bytes already stored under it must match the new ones.
+    fn retain_image(&mut self, image_hash: String, image_data: Vec<u8>) -> Result<(), PlatformError> {
+        self.check_image_bytes(&image_hash, &image_data)?;
+        self.images.entry(image_hash).or_insert((image_data, 0)).1 += 1;
+        Ok(())
+    }
+
+    // Make sure storing these bytes under the hash wouldn't conflate them with different stored bytes
+    fn check_image_bytes(&self, image_hash: &str, image_data: &[u8]) -> Result<(), PlatformError> {
+        match self.image_data(image_hash) {
+            Some(stored) if stored != image_data => Err(PlatformError::HashCollision {
+                hash: image_hash.to_string(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    // Drop a reference to an image, freeing its bytes once nothing references it
+    fn release_image(&mut self, image_hash: &str) {
+        if let Some((_, refs)) = self.images.get_mut(image_hash) {
+            *refs = refs.saturating_sub(1);
+            if *refs == 0 {
+                self.images.remove(image_hash);
+                self.compressed_images.remove(image_hash);
+            }
+        }
+    }
+
+    // Switch a stored image over to compressed bytes, unless it already is or compressing doesn't make it smaller
+    fn compress_image(&mut self, image_hash: &str) {
+        if self.compressed_images.contains(image_hash) {
+            return;
+        }
+        if let Some((image_data, _)) = self.images.get_mut(image_hash) {
+            let compressed = lz4_flex::block::compress_prepend_size(image_data);
+            if compressed.len() < image_data.len() {
+                *image_data = compressed;
+                self.compressed_images.insert(image_hash.to_string());
+            }
+        }
+    }
+
+    // The original bytes of a stored image, decompressed if necessary. Compressed images start with their
+    // original length, and the decoder fails rather than produce more bytes than that.
+    fn image_data(&self, image_hash: &str) -> Option<Vec<u8>> {
+        let (image_data, _) = self.images.get(image_hash)?;
+        if !self.compressed_images.contains(image_hash) {
+            return Some(image_data.clone());
+        }
+        lz4_flex::block::decompress_size_prepended(image_data).ok()
+    }
+
+    // Length of a stored image's original bytes, without decompressing them
+    fn original_image_len(&self, image_hash: &str) -> Option<u64> {
+        let (image_data, _) = self.images.get(image_hash)?;
+        if !self.compressed_images.contains(image_hash) {
+            return Some(image_data.len() as u64);
+        }
+        let (len, _) = lz4_flex::block::uncompressed_size(image_data).ok()?;
+        Some(len as u64)
+    }
+
+    // Set `image_size_bytes` on properties saved before it was tracked, from the images still stored.
+    // Every image is non-empty, so a zero size only ever means the field was never filled in.
+    fn backfill_image_sizes(&mut self) {
+        let mut sizes = Vec::new();
+        for property in self.properties.values().chain(self.trash.values().cloned()) {
+            if property.image_size_bytes == 0 {
+                let size = property.image_hashes.iter().filter_map(|hash| self.original_image_len(hash)).sum();
+                sizes.push((property.id.clone(), size));
+            }
+        }
+        for (property_id, size) in sizes {
+            if let Some(mut property) = self.properties.get_mut(&property_id) {
+                property.image_size_bytes = size;
+                property.commit();
+            } else if let Some(property) = self.trash.get_mut(&property_id) {
+                property.image_size_bytes = size;
+            }
+        }
+    }
+
+    // Recompute every image's reference count from the stored properties, freeing unreferenced images.
+    // Used when the images come from somewhere that didn't track counts, such as a snapshot or older state.
+    fn recount_image_refs(&mut self) {
+        for (_, refs) in self.images.values_mut() {
+            *refs = 0;
+        }
+        for property in self.properties.values().chain(self.trash.values().cloned()) {
+            for hash in property.stored_hashes() {
+                if let Some((_, refs)) = self.images.get_mut(hash) {
+                    *refs += 1;
+                }
+            }
+        }
+        self.images.retain(|_, (_, refs)| *refs > 0);
+        let images = &self.images;
+        self.compressed_images.retain(|hash| images.contains_key(hash));
+    }
+}
+
+// One of the virtual memories stable memory is split into
+type Memory = VirtualMemory<DefaultMemoryImpl>;
+
+// Helper function to open one of the virtual memories
+fn memory(id: MemoryId) -> Memory {
+    MEMORY_MANAGER.with(|manager| manager.borrow().get(id))
+}
+
+// Global state using thread-local storage and RefCell (canisters run single-threaded)
+
+thread_local! {
+    // Splits stable memory between the property map and the rest of the state saved around upgrades
+    static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> =
+        RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
+    static DECENTRALIZED_PLATFORM: RefCell<DecentralizedPlatform> = RefCell::new(DecentralizedPlatform::default());
+    // Privileged principal allowed to configure the platform, captured at install time
+    static ADMIN: RefCell<Option<Principal>> = const { RefCell::new(None) };
+    // Digest of each live property (see `property_digest`), published through the certified hash tree
+    static CERTIFIED_DIGESTS: RefCell<RbTree<String, Hash>> = const { RefCell::new(RbTree::new()) };
+    // Open chunked uploads keyed by session ID, plus a counter that keeps session IDs unique
+    static UPLOAD_SESSIONS: RefCell<HashMap<String, UploadSession>> = RefCell::new(HashMap::new());
+    static UPLOAD_SESSION_COUNTER: RefCell<u64> = const { RefCell::new(0) };
+    // Snapshot pages and image bytes sent for `import_state` and not yet loaded; heap only, like uploads
+    static STAGED_IMPORT: RefCell<StagedImport> = RefCell::new(StagedImport::default());
+    // Timestamps of each principal's recent uploads, oldest first, used for rate limiting.
+    // All anonymous callers share `Principal::anonymous()`, so together they get a single allowance.
+    static UPLOAD_HISTORY: RefCell<HashMap<Principal, Vec<u64>>> = RefCell::new(HashMap::new());
+    // Results of recent uploads keyed by caller and idempotency key, with when they completed; like the
+    // rate-limit history, these don't survive upgrades
+    static IDEMPOTENT_UPLOADS: RefCell<HashMap<(Principal, String), (u64, UploadResult)>> = RefCell::new(HashMap::new());
+    // Listings a buyer is paying for right now, with when the payment started. The owner can't move or
+    // withdraw them until the sale settles; entries older than RESERVATION_TTL_NANOS are ignored, in case
+    // the settling step never ran.
+    static SALES_IN_FLIGHT: RefCell<HashMap<String, u64>> = RefCell::new(HashMap::new());
+    // Periodic task releasing lapsed reservations; timers don't survive upgrades, so it is re-armed in post_upgrade
+    static RESERVATION_SWEEP_TIMER: RefCell<Option<TimerId>> = const { RefCell::new(None) };
+    // Secondary index of live property IDs by owner, rebuilt from the primary store after a restore
+    static OWNER_INDEX: RefCell<OwnerIndex> = RefCell::new(OwnerIndex::default());
+}
+
+// Helpers to read or change the platform state for the duration of a closure. Async endpoints use them so no
+// borrow lives across an `.await`: they copy out what they need before the call and look the state up again
+// afterwards, since another message may have changed it in between. A `RefCell` borrow held over an await would
+// make every message interleaved at that point trap with "already borrowed".
+fn with_state<R>(f: impl FnOnce(&DecentralizedPlatform) -> R) -> R {
+    DECENTRALIZED_PLATFORM.with(|platform| f(&platform.borrow()))
+}
+
+fn with_state_mut<R>(f: impl FnOnce(&mut DecentralizedPlatform) -> R) -> R {
+    DECENTRALIZED_PLATFORM.with(|platform| f(&mut platform.borrow_mut()))
+}
+
+// Live property IDs grouped by owner, plus the owner each ID is currently filed under
+#[derive(Default)]
+struct OwnerIndex {
+    owner_index: HashMap<Principal, HashSet<String>>,
+    indexed_owner: HashMap<String, Principal>,
+}
+
+// Settings passed at install time, and again on upgrade to re-apply them; every field is optional
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+struct InitArgs {
+    admin: Option<Principal>, // Defaults to the installing principal
+    max_image_bytes: Option<u64>,
+    max_properties_per_owner: Option<u64>,
+    hash_salt: Option<Vec<u8>>, // Only used at install; changing it later would break the stored hashes
+}
+
+#[init]
+fn init(args: Option<InitArgs>) {
+    let args = args.unwrap_or_default();
+    // The deploying principal becomes the admin unless the install names another one, so there is
+    // no window in which someone else could claim the role
+    ADMIN.with(|admin| *admin.borrow_mut() = Some(args.admin.unwrap_or_else(env::caller)));
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        platform.hash_salt = args.hash_salt.clone().filter(|salt| !salt.is_empty());
+        apply_limits(&mut platform, &args);
+        certify_all(&platform);
+        index_all(&platform);
+    });
+    start_reservation_sweep();
+}
+
+// Helper function to apply the limits given in init or upgrade arguments; unset ones keep their current value
+fn apply_limits(platform: &mut DecentralizedPlatform, args: &InitArgs) {
+    if let Some(limit) = args.max_image_bytes {
+        platform.max_image_bytes = Some(limit);
+    }
+    if let Some(limit) = args.max_properties_per_owner {
+        platform.max_properties_per_owner = Some(limit);
+    }
+}
+
+// Helper function that traps unless the caller is the admin
+fn require_admin() {
+    if !is_admin(env::caller()) {
+        env::trap("Only the admin can perform this action.");
+    }
+}
+
+// Helper function to trap unless the caller is a registered verifier
+fn require_verifier() {
+    let caller = env::caller();
+    if !DECENTRALIZED_PLATFORM.with(|platform| platform.borrow().verifiers.contains(&caller)) {
+        env::trap("Only a registered verifier can perform this action.");
+    }
+}
+
+// Helper function to check whether a principal is the admin
+fn is_admin(principal: Principal) -> bool {
+    ADMIN.with(|admin| *admin.borrow()) == Some(principal)
+}
+
+// Helper function to check whether a principal is the primary admin or on the admin council
+fn is_council_member(principal: Principal) -> bool {
+    is_admin(principal) || DECENTRALIZED_PLATFORM.with(|platform| platform.borrow().admin_council.contains(&principal))
+}
+
+// Helper function to trap unless the caller is the primary admin or on the admin council
+fn require_council_member() {
+    if !is_council_member(env::caller()) {
+        env::trap("Only an admin can perform this action.");
+    }
+}
+
+// Helper function to trap when destructive actions need several admins, so they go through `propose_action`
+fn require_sole_approval() {
+    let threshold = DECENTRALIZED_PLATFORM.with(|platform| platform.borrow().approval_threshold);
+    if threshold > 1 {
+        env::trap(&format!(
+            "This action needs approval from {} admins; use `propose_action` instead.",
+            threshold
+        ));
+    }
+}
+
+// Helper function to trap unless the caller is signed in, so every record has a principal that can manage it
+fn require_authenticated() {
+    if env::caller() == Principal::anonymous() {
+        env::trap("Anonymous callers cannot modify the platform.");
+    }
+}
+
+// Helper function for endpoints that return errors: like `require_authenticated`, but reports an anonymous
+// caller as `PlatformError::Unauthorized` instead of trapping
+fn ensure_authenticated() -> Result<(), PlatformError> {
+    if env::caller() == Principal::anonymous() {
+        return Err(PlatformError::Unauthorized);
+    }
+    Ok(())
+}
+
+// Helper function to trap while the admin has paused the platform; admin endpoints stay usable for maintenance
+fn require_not_paused() {
+    if DECENTRALIZED_PLATFORM.with(|platform| platform.borrow().paused) {
+        env::trap("The platform is paused for maintenance.");
+    }
+}
+
+// Helper function for endpoints that return errors: like `require_not_paused`, but reports the pause as
+// `PlatformError::Paused` instead of trapping
+fn ensure_not_paused() -> Result<(), PlatformError> {
+    if DECENTRALIZED_PLATFORM.with(|platform| platform.borrow().paused) {
+        return Err(PlatformError::Paused);
+    }
+    Ok(())
+}
+
+// Query function to get the admin principal, if one has been set
+#[query]
+fn get_admin() -> Option<Principal> {
+    ADMIN.with(|admin| *admin.borrow())
+}
+
+// Query function for liveness probes: "ok" and the current time in nanoseconds, without reading any platform state
+#[query]
+fn ping() -> String {
+    format!("ok {}", env::time())
+}
+
+// Query function for readiness probes: whether an admin has been set and the platform isn't paused
+#[query]
+fn ready() -> bool {
+    get_admin().is_some() && ensure_not_paused().is_ok()
+}
+
+// Save everything but the properties, which already live in stable memory, before the code is swapped out
+#[pre_upgrade]
+fn pre_upgrade() {
+    let mut encoded = Vec::new();
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let admin = ADMIN.with(|admin| *admin.borrow());
+        ciborium::into_writer(&(&*platform.borrow(), admin), &mut encoded).expect("Failed to encode the platform");
+    });
+    // The length comes first so `post_upgrade` knows how much to read back
+    let mut state = memory(STATE_MEMORY_ID);
+    let mut writer = ic_stable_structures::writer::Writer::new(&mut state, 0);
+    writer
+        .write(&(encoded.len() as u64).to_le_bytes())
+        .and_then(|_| writer.write(&encoded))
+        .expect("Failed to save platform to stable memory");
+}
+
+// Helper function to read back the platform and admin saved by `pre_upgrade`, or `None` if nothing was saved,
+// as when upgrading from a version that never wrote stable memory
+fn restore_saved_state() -> Option<(DecentralizedPlatform, Option<Principal>)> {
+    let state = memory(STATE_MEMORY_ID);
+    if state.size() == 0 {
+        return None;
+    }
+    let mut len = [0u8; 8];
+    state.read(0, &mut len);
+    let mut encoded = vec![0u8; u64::from_le_bytes(len) as usize];
+    state.read(len.len() as u64, &mut encoded);
+    Some(ciborium::from_reader(encoded.as_slice()).expect("Failed to restore platform from stable memory"))
+}
+
+// Restore the platform saved by `pre_upgrade`
+#[post_upgrade]
+fn post_upgrade(args: Option<InitArgs>) {
+    let args = args.unwrap_or_default();
+    let Some((restored, admin)) = restore_saved_state() else {
+        ADMIN.with(|admin| *admin.borrow_mut() = Some(args.admin.unwrap_or_else(env::caller)));
+        DECENTRALIZED_PLATFORM.with(|platform| {
+            let mut platform = platform.borrow_mut();
+            apply_limits(&mut platform, &args);
+            certify_all(&platform);
+            index_all(&platform);
+        });
+        start_reservation_sweep();
+        return;
+    };
+
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        *platform.borrow_mut() = restored;
+        normalize_stored_ids(&mut platform.borrow_mut());
+        platform.borrow_mut().backfill_image_sizes();
+        // The salt isn't re-applied: the restored hashes were made with the one chosen at install
+        apply_limits(&mut platform.borrow_mut(), &args);
+        certify_all(&platform.borrow());
+        index_all(&platform.borrow());
+    });
+    // An admin named in the upgrade arguments takes over; otherwise the saved one stays, or the upgrading
+    // controller if none was saved.
+    ADMIN.with(|current| *current.borrow_mut() = args.admin.or(admin).or(Some(env::caller())));
+    start_reservation_sweep();
+}
+
+// Helper function to (re)start the periodic reservation sweep at the configured interval.
+// Any previous timer is cancelled first, so there is never more than one sweep scheduled.
+fn start_reservation_sweep() {
+    let secs = DECENTRALIZED_PLATFORM.with(|platform| platform.borrow().reservation_sweep_secs());
+    let timer = env::set_timer_interval(Duration::from_secs(secs), sweep_expired_reservations);
+    if let Some(previous) = RESERVATION_SWEEP_TIMER.with(|current| current.borrow_mut().replace(timer)) {
+        env::clear_timer(previous);
+    }
+}
+
+// Helper function run by the timer to free listings whose reservation has lapsed.
+// Only reservations that are still present and past expiry are touched, so repeated runs are harmless.
+fn sweep_expired_reservations() {
+    let now = env::time();
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let released: Vec<(String, Property)> = platform
+            .properties
+            .iter()
+            // A hold whose payment is still in flight lapses once the sale settles instead
+            .filter(|(property_id, _)| !sale_in_flight(property_id, now))
+            .filter_map(|(property_id, mut property)| {
+                property.expire_reservation(now).then_some((property_id, property))
+            })
+            .collect();
+        for (property_id, property) in released {
+            platform.properties.insert(property_id.clone(), property);
+            platform.record_event(AuditEventKind::ReservationExpired, &property_id);
+        }
+    });
+}
+
+// Helper function to compute the digest certified for a property. It covers the ID, type label, image hashes
+// (comma-separated), hash algorithm, description, owner, price, status, visibility, view count, and creation
+// and update times, in that order; each is hashed as its big-endian u64 length followed by its bytes, with
+// numbers as big-endian u64s and enums by variant name. Clients can recompute it from the fields alone.
+fn property_digest(property: &Property) -> Hash {
+    let visibility = match &property.visibility {
+        Visibility::Public => "Public".to_string(),
+        Visibility::Private(viewers) => {
+            let viewers: Vec<String> = viewers.iter().map(Principal::to_text).collect();
+            format!("Private:{}", viewers.join(","))
+        }
+    };
+    let fields: [Vec<u8>; 12] = [
+        property.id.clone().into_bytes(),
+        type_label(&property.property_type).into_bytes(),
+        property.image_hashes.join(",").into_bytes(),
+        format!("{:?}", property.hash_algo).into_bytes(),
+        property.description.clone().into_bytes(),
+        property.owner.as_slice().to_vec(),
+        property.price_e8s.to_be_bytes().to_vec(),
+        format!("{:?}", property.status).into_bytes(),
+        visibility.into_bytes(),
+        property.views.to_be_bytes().to_vec(),
+        property.created_at.to_be_bytes().to_vec(),
+        property.updated_at.to_be_bytes().to_vec(),
+    ];
+    let mut hasher = Sha256::new();
+    for field in fields {
+        hasher.update((field.len() as u64).to_be_bytes());
+        hasher.update(field);
+    }
+    hasher.finalize().into()
+}
+
+// Helper function to publish the root of the certified tree holding the given digests
+fn publish_certified_root(digests: &RbTree<String, Hash>) {
+    let root = ic_certified_map::labeled_hash(CERTIFIED_PROPERTIES_LABEL, &digests.root_hash());
+    env::set_certified_data(&root);
+}
+
+// Helper function to refresh the certified digest of one property after it changed or went away
+fn certify_property(platform: &DecentralizedPlatform, property_id: &str) {
+    CERTIFIED_DIGESTS.with(|digests| {
+        let mut digests = digests.borrow_mut();
+        match platform.properties.get(property_id) {
+            Some(property) => digests.insert(property_id.to_string(), property_digest(&property)),
+            None => digests.delete(property_id.as_bytes()),
+        }
+        publish_certified_root(&digests);
+    });
+}
+
+// Helper function to rebuild every certified digest, e.g. after the platform was restored
+fn certify_all(platform: &DecentralizedPlatform) {
+    CERTIFIED_DIGESTS.with(|digests| {
+        let mut digests = digests.borrow_mut();
+        *digests = RbTree::new();
+        for (id, property) in platform.properties.iter() {
+            digests.insert(id, property_digest(&property));
+        }
+        publish_certified_root(&digests);
+    });
+}
+
+// Helper function to file a property under its current owner, or drop it from the index once it is no longer live
+fn index_owner(platform: &DecentralizedPlatform, property_id: &str) {
+    OWNER_INDEX.with(|index| {
+        let mut index = index.borrow_mut();
+        let owner = platform.properties.get(property_id).map(|property| property.owner);
+        let previous = match owner {
+            Some(owner) => index.indexed_owner.insert(property_id.to_string(), owner),
+            None => index.indexed_owner.remove(property_id),
+        };
+        if previous == owner {
+            return;
+        }
+        if let Some(previous) = previous {
+            if let Some(ids) = index.owner_index.get_mut(&previous) {
+                ids.remove(property_id);
+                if ids.is_empty() {
+                    index.owner_index.remove(&previous);
+                }
+            }
+        }
+        if let Some(owner) = owner {
+            index.owner_index.entry(owner).or_default().insert(property_id.to_string());
+        }
+    });
+}
+
+// Helper function to get the per-owner limit if `owner` already holds that many properties, so another one
+// would take them over it
+fn quota_reached(platform: &DecentralizedPlatform, owner: Principal) -> Option<u64> {
+    let limit = platform.max_properties_per_owner?;
+    let held = OWNER_INDEX.with(|index| index.borrow().owner_index.get(&owner).map_or(0, HashSet::len)) as u64;
+    (held >= limit).then_some(limit)
+}
+
+// Helper function to get the platform-wide limit if the platform already holds that many live properties
+fn platform_full(platform: &DecentralizedPlatform) -> Option<u64> {
+    let limit = platform.max_total_properties?;
+    (platform.properties.len() as u64 >= limit).then_some(limit)
+}
+
+// Helper function to rebuild the owner index from scratch, e.g. after the platform was restored
+fn index_all(platform: &DecentralizedPlatform) {
+    OWNER_INDEX.with(|index| {
+        let mut index = index.borrow_mut();
+        *index = OwnerIndex::default();
+        for (id, property) in platform.properties.iter() {
+            index.owner_index.entry(property.owner).or_default().insert(id.clone());
+            index.indexed_owner.insert(id, property.owner);
+        }
+    });
+}
+
+// Helper function to assert that the owner index agrees with the primary store. It rescans every property, so
+// it only runs in unit tests, after every event.
+#[cfg(test)]
+fn check_owner_index(platform: &DecentralizedPlatform) {
+    OWNER_INDEX.with(|index| {
+        let index = index.borrow();
+        assert_eq!(index.indexed_owner.len(), platform.properties.len());
+        for (id, property) in platform.properties.iter() {
+            assert_eq!(index.indexed_owner.get(&id), Some(&property.owner));
+            assert!(index.owner_index.get(&property.owner).is_some_and(|ids| ids.contains(&id)));
+        }
+        let indexed: usize = index.owner_index.values().map(HashSet::len).sum();
+        assert_eq!(indexed, platform.properties.len());
+    });
+}
+
+// Function to upload an image, hash it, and store property data
+#[update]
+fn upload_property(
+    property_id: String,
+    property_type: PropertyType,
+    images: Vec<Vec<u8>>,
+    description: String,
+    price_e8s: u64,
+    hash_algo: Option<HashAlgo>,
+    options: Option<UploadOptions>,
+) -> Result<UploadResult, PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    upload(UploadRequest {
+        property_id,
+        property_type,
+        images,
+        description,
+        price_e8s,
+        hash_algo,
+        options,
+    })
+}
+
+// Function to upload a property under an ID generated from its primary image, the caller, and the time.
+// Returns the new ID so the caller can refer to the property afterwards.
+#[update]
+fn upload_property_auto_id(
+    property_type: PropertyType,
+    images: Vec<Vec<u8>>,
+    description: String,
+    price_e8s: u64,
+    hash_algo: Option<HashAlgo>,
+    options: Option<UploadOptions>,
+) -> Result<String, PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let salt = DECENTRALIZED_PLATFORM.with(|platform| platform.borrow().hash_salt().to_vec());
+    let primary_hash = images
+        .first()
+        .map(|image_data| hash_image(image_data, hash_algo.unwrap_or_default(), &salt))
+        .unwrap_or_default();
+    let property_id = generate_property_id(&primary_hash, env::caller(), env::time());
+    // A retry with the same idempotency key gets back the ID generated the first time
+    let result = upload(UploadRequest {
+        property_id,
+        property_type,
+        images,
+        description,
+        price_e8s,
+        hash_algo,
+        options,
+    })?;
+    Ok(result.property.id)
+}
+
+// Helper function to derive a fresh property ID: the first 16 hex characters of a SHA-256 over the inputs.
+// Calls in the same round share a timestamp, so an attempt counter is mixed in until the ID is unused.
+fn generate_property_id(image_hash: &str, caller: Principal, now: u64) -> String {
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        (0u64..)
+            .map(|attempt| {
+                let mut hasher = Sha256::new();
+                hasher.update(image_hash.as_bytes());
+                hasher.update(caller.as_slice());
+                hasher.update(now.to_be_bytes());
+                if attempt > 0 {
+                    hasher.update(attempt.to_be_bytes());
+                }
+                hex::encode(&hasher.finalize()[..8])
+            })
+            .find(|id| !platform.properties.contains_key(id) && !platform.trash.contains_key(id))
+            .expect("Some attempt yields an unused ID")
+    })
+}
+
+// Function to upload several properties at once; each item succeeds or fails on its own, so once the platform
+// is full the remaining items fail with `PlatformFull`. The whole batch fails while the platform is paused.
+#[update]
+fn upload_properties_batch(
+    items: Vec<UploadRequest>,
+) -> Result<Vec<Result<UploadResult, PlatformError>>, PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    if items.len() > MAX_BATCH_LEN {
+        return Err(PlatformError::InvalidInput(format!("A batch can contain at most {} items.", MAX_BATCH_LEN)));
+    }
+    Ok(items.into_iter().map(upload).collect())
+}
+
+// Function to start a chunked upload for images too large for a single message; returns the session ID
+#[update]
+fn begin_upload(property_id: String, total_chunks: u32) -> Result<String, PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let property_id = normalize_id(&property_id);
+    if total_chunks == 0 || total_chunks > MAX_UPLOAD_CHUNKS {
+        return Err(PlatformError::InvalidInput(format!(
+            "An upload must have between 1 and {} chunks.",
+            MAX_UPLOAD_CHUNKS
+        )));
+    }
+
+    let uploader = env::caller();
+    let now = env::time();
+    let counter = UPLOAD_SESSION_COUNTER.with(|counter| {
+        let mut counter = counter.borrow_mut();
+        *counter += 1;
+        *counter
+    });
+    let mut hasher = Sha256::new();
+    hasher.update(uploader.as_slice());
+    hasher.update(property_id.as_bytes());
+    hasher.update(now.to_be_bytes());
+    hasher.update(counter.to_be_bytes());
+    let session_id = hex::encode(&hasher.finalize()[..16]);
+
+    UPLOAD_SESSIONS.with(|sessions| {
+        let mut sessions = sessions.borrow_mut();
+        // Drop abandoned sessions so their chunks don't pile up
+        sessions.retain(|_, session| now.saturating_sub(session.started_at) < UPLOAD_SESSION_TTL_NANOS);
+        sessions.insert(
+            session_id.clone(),
+            UploadSession {
+                uploader,
+                property_id,
+                total_chunks,
+                chunks: BTreeMap::new(),
+                started_at: now,
+            },
+        );
+    });
+    Ok(session_id)
+}
+
+// Function to store one chunk of a chunked upload; re-sending an index replaces that chunk
+#[update]
+fn push_chunk(session: String, index: u32, data: Vec<u8>) -> Result<(), PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let max_image_bytes = DECENTRALIZED_PLATFORM.with(|platform| platform.borrow().largest_image_limit());
+    UPLOAD_SESSIONS.with(|sessions| {
+        let mut sessions = sessions.borrow_mut();
+        let upload = sessions
+            .get_mut(&session)
+            .filter(|upload| upload.uploader == env::caller())
+            .ok_or(PlatformError::NotFound)?;
+        if index >= upload.total_chunks {
+            return Err(PlatformError::InvalidInput(format!("Chunk {} is out of range.", index)));
+        }
+
+        let received: u64 = upload
+            .chunks
+            .iter()
+            .filter(|(existing, _)| **existing != index)
+            .map(|(_, chunk)| chunk.len() as u64)
+            .sum();
+        if received + data.len() as u64 > max_image_bytes {
+            return Err(PlatformError::TooLarge {
+                limit_bytes: max_image_bytes,
+            });
+        }
+        upload.chunks.insert(index, data);
+        Ok(())
+    })
+}
+
+// Function to assemble a chunked upload in order, then hash and store it like `upload_property`
+#[update]
+fn finish_upload(
+    session: String,
+    property_type: PropertyType,
+    description: String,
+    price_e8s: u64,
+) -> Result<UploadResult, PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let (property_id, image_data) = UPLOAD_SESSIONS.with(|sessions| {
+        let sessions = sessions.borrow();
+        let upload = sessions
+            .get(&session)
+            .filter(|upload| upload.uploader == env::caller())
+            .ok_or(PlatformError::NotFound)?;
+        if let Some(index) = (0..upload.total_chunks).find(|index| !upload.chunks.contains_key(index)) {
+            return Err(PlatformError::InvalidInput(format!("Chunk {} is missing.", index)));
+        }
+        Ok((upload.property_id.clone(), upload.chunks.values().flatten().copied().collect()))
+    })?;
+
+    let result = upload(UploadRequest {
+        property_id,
+        property_type,
+        images: vec![image_data],
+        description,
+        price_e8s,
+        hash_algo: None,
+        options: None,
+    })?;
+    UPLOAD_SESSIONS.with(|sessions| sessions.borrow_mut().remove(&session));
+    Ok(result)
+}
+
+// Helper function that validates, hashes, and stores a single upload
+fn upload(request: UploadRequest) -> Result<UploadResult, PlatformError> {
+    let UploadRequest {
+        property_id,
+        property_type,
+        images,
+        description,
+        price_e8s,
+        hash_algo,
+        options,
+    } = request;
+    let hash_algo = hash_algo.unwrap_or_default();
+    let options = options.unwrap_or_default();
+    // A retry is answered before any other check, so it can't be refused for limits the first call used up
+    let idempotency_key = options.idempotency_key.clone();
+    if let Some(key) = &idempotency_key {
+        if key.len() > MAX_IDEMPOTENCY_KEY_LEN {
+            return Err(PlatformError::InvalidInput("The idempotency key is too long.".to_string()));
+        }
+        if let Some(result) = remembered_upload(env::caller(), key, env::time()) {
+            return Ok(result);
+        }
+    }
+    let property_id = normalize_id(&property_id);
+    if property_id.is_empty() {
+        return Err(PlatformError::InvalidInput("The property ID is empty.".to_string()));
+    }
+    let property_type = normalize_property_type(property_type)?;
+    if !DECENTRALIZED_PLATFORM.with(|platform| platform.borrow().allows_type(&property_type)) {
+        return Err(PlatformError::InvalidInput("Uploads of this property type are disabled.".to_string()));
+    }
+    if !DECENTRALIZED_PLATFORM.with(|platform| platform.borrow().is_seller(env::caller())) {
+        return Err(PlatformError::Unauthorized);
+    }
+    if let Some(limit) = DECENTRALIZED_PLATFORM.with(|platform| quota_reached(&platform.borrow(), env::caller())) {
+        return Err(PlatformError::QuotaExceeded { limit });
+    }
+    if let Some(limit) = DECENTRALIZED_PLATFORM.with(|platform| platform_full(&platform.borrow())) {
+        return Err(PlatformError::PlatformFull { limit });
+    }
+    let blocked = DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        platform.contains_blocked_keyword(&description) || platform.contains_blocked_keyword(&type_label(&property_type))
+    });
+    if blocked {
+        return Err(PlatformError::InvalidInput("The listing contains a blocked keyword.".to_string()));
+    }
+
+    // Bound caller-chosen strings so they can't be used to bloat canister memory
+    let type_label_len = match &property_type {
+        PropertyType::Other(label) => label.len(),
+        _ => 0,
+    };
+    let oversized_field = [
+        ("property_id", property_id.len(), MAX_PROPERTY_ID_LEN),
+        ("description", description.len(), MAX_DESCRIPTION_LEN),
+        ("property_type", type_label_len, MAX_TYPE_LABEL_LEN),
+        ("key_id", options.key_id.as_ref().map_or(0, String::len), MAX_KEY_ID_LEN),
+    ]
+    .into_iter()
+    .find(|(_, len, max)| len > max);
+    if let Some((field, _, _)) = oversized_field {
+        return Err(PlatformError::InvalidInput(format!("The {} is too long.", field)));
+    }
+
+    // Later duplicates of an attribute key overwrite earlier ones
+    let attributes: BTreeMap<String, String> = options.attributes.unwrap_or_default().into_iter().collect();
+    if !attributes_within_limits(&attributes) {
+        return Err(PlatformError::LimitReached(format!(
+            "A property can have at most {} attributes totalling {} bytes.",
+            MAX_ATTRIBUTES, MAX_ATTRIBUTES_BYTES
+        )));
+    }
+    let missing_attribute = DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        let required = platform.required_attributes.get(&type_label(&property_type))?;
+        required.iter().find(|key| !attributes.contains_key(*key)).cloned()
+    });
+    if let Some(key) = missing_attribute {
+        return Err(PlatformError::MissingAttribute { key });
+    }
+    if options.location.as_ref().is_some_and(|location| !location.is_valid()) {
+        return Err(PlatformError::InvalidInput("The location is out of range.".to_string()));
+    }
+    let royalty_bps = options.royalty_bps.unwrap_or(0);
+    if royalty_bps > MAX_ROYALTY_BPS {
+        return Err(PlatformError::InvalidInput(format!(
+            "The royalty can be at most {} basis points.",
+            MAX_ROYALTY_BPS
+        )));
+    }
+    let mut tags: Vec<String> = Vec::new();
+    for tag in options.tags.unwrap_or_default() {
+        let tag = normalize_tag(&tag);
+        if tag.is_empty() {
+            return Err(PlatformError::InvalidInput("Tags must contain at least one letter or digit.".to_string()));
+        }
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    if tags.len() > MAX_TAGS {
+        return Err(PlatformError::LimitReached(format!("A property can have at most {} tags.", MAX_TAGS)));
+    }
+
+    let now = env::time();
+    let owner = env::caller();
+    check_upload_rate(owner, now)?;
+    if options.expires_at.is_some_and(|expires_at| expires_at <= now) {
+        return Err(PlatformError::InvalidInput("The expiry must be in the future.".to_string()));
+    }
+
+    // Validate image data
+    if images.is_empty() || images.iter().any(|image_data| image_data.is_empty()) {
+        return Err(PlatformError::EmptyImage);
+    }
+    let (max_image_bytes, salt) = DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        (platform.max_image_bytes_for(&property_type), platform.hash_salt().to_vec())
+    });
+    if images.iter().any(|image_data| image_data.len() as u64 > max_image_bytes) {
+        return Err(PlatformError::TooLarge {
+            limit_bytes: max_image_bytes,
+        });
+    }
+    // Ciphertext has no recognizable format, so encrypted uploads skip detection and store no format
+    let encrypted = options.encrypted.unwrap_or(false);
+    let compress = options.compress.unwrap_or(false);
+    let formats: Vec<ImageFormat> = if encrypted {
+        Vec::new()
+    } else {
+        images
+            .iter()
+            .map(|image_data| detect_format(image_data))
+            .collect::<Option<_>>()
+            .ok_or_else(|| PlatformError::InvalidInput("The image format is not supported.".to_string()))?
+    };
+
+    // The thumbnail gets the same checks as the full images, with a tighter size limit
+    let thumbnail = match options.thumbnail {
+        Some(thumbnail_data) => {
+            if thumbnail_data.is_empty() {
+                return Err(PlatformError::EmptyImage);
+            }
+            if thumbnail_data.len() as u64 > MAX_THUMBNAIL_BYTES {
+                return Err(PlatformError::TooLarge {
+                    limit_bytes: MAX_THUMBNAIL_BYTES,
+                });
+            }
+            if !encrypted && detect_format(&thumbnail_data).is_none() {
+                return Err(PlatformError::InvalidInput("The image format is not supported.".to_string()));
+            }
+            Some((hash_image(&thumbnail_data, hash_algo, &salt), thumbnail_data))
+        }
+        None => None,
+    };
+
+    // Hash each image with the chosen algorithm, skipping repeats of the same image
+    let mut hashes: Vec<String> = Vec::new();
+    let mut unique_images = Vec::new();
+    for image_data in images {
+        let hash = hash_image(&image_data, hash_algo, &salt);
+        if !hashes.contains(&hash) {
+            hashes.push(hash);
+            unique_images.push(image_data);
+        }
+    }
+
+    // Create a new Property with the given ID, type, and other details, owned by the caller
+    let property = Property {
+        id: property_id.clone(),
+        property_type,
+        image_hashes: hashes.clone(),
+        hash_algo,
+        format: formats.first().copied(),
+        description,
+        owner,
+        created_at: now,
+        updated_at: now,
+        price_e8s,
+        status: ListingStatus::Available,
+        owner_history: vec![OwnershipRecord {
+            owner,
+            acquired_at: now,
+        }],
+        reservation: None,
+        attributes,
+        location: options.location,
+        tags,
+        approvals: Vec::new(),
+        shares: sole_shareholder(owner),
+        expires_at: options.expires_at,
+        reviews: Vec::new(),
+        verified: false,
+        auction: None,
+        thumbnail_hash: thumbnail.as_ref().map(|(hash, _)| hash.clone()),
+        visibility: options.visibility.unwrap_or_default(),
+        signature: None,
+        pubkey: None,
+        transfers: Vec::new(),
+        featured: false,
+        encrypted,
+        key_id: options.key_id.filter(|_| encrypted),
+        views: 0,
+        creator: owner,
+        royalty_bps,
+        image_size_bytes: unique_images.iter().map(|image_data| image_data.len() as u64).sum(),
+    };
+
+    // Safely store the property in the decentralized platform, refusing to overwrite an existing ID
+    let duplicate_of = DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        // Trashed properties still hold their ID so they can be restored later
+        if platform.properties.contains_key(&property_id) || platform.trash.contains_key(&property_id) {
+            return Err(PlatformError::DuplicateId { id: property_id });
+        }
+
+        // Report other listings sharing any of these images so the UI can flag possible duplicates. Only an image
+        // that is already stored can be shared, so the listings are only searched when the image table says one is.
+        let already_stored = hashes.iter().any(|hash| platform.images.contains_key(hash));
+        let mut duplicate_of: Vec<String> = if already_stored {
+            platform
+                .properties
+                .values()
+                .filter(|existing| existing.image_hashes.iter().any(|hash| hashes.contains(hash)))
+                .map(|existing| existing.id.clone())
+                .collect()
+        } else {
+            Vec::new()
+        };
+        duplicate_of.sort();
+        if platform.block_duplicate_images {
+            if let Some(existing_id) = duplicate_of.first() {
+                return Err(PlatformError::DuplicateImage {
+                    existing_id: existing_id.clone(),
+                });
+            }
+        }
+
+        let new_images: Vec<(&String, &Vec<u8>)> = hashes.iter().zip(&unique_images).collect();
+        for (hash, image_data) in new_images.into_iter().chain(thumbnail.as_ref().map(|(hash, data)| (hash, data))) {
+            platform.check_image_bytes(hash, image_data)?;
+        }
+
+        platform.properties.insert(property_id.clone(), property.clone());
+        for (hash, image_data) in hashes.iter().zip(unique_images) {
+            platform.retain_image(hash.clone(), image_data).expect("Image bytes checked above");
+            if compress {
+                platform.compress_image(hash);
+            }
+        }
+        if let Some((hash, thumbnail_data)) = thumbnail {
+            platform.retain_image(hash, thumbnail_data).expect("Thumbnail bytes checked above");
+        }
+        platform.record_event(AuditEventKind::Uploaded, &property_id);
+        Ok(duplicate_of)
+    })?;
+
+    UPLOAD_HISTORY.with(|history| history.borrow_mut().entry(owner).or_default().push(now));
+
+    // Return the new property and its image hashes along with any existing listings of the same images
+    let result = UploadResult {
+        hashes,
+        duplicate_of,
+        property,
+    };
+    if let Some(key) = idempotency_key {
+        IDEMPOTENT_UPLOADS.with(|uploads| uploads.borrow_mut().insert((owner, key), (now, result.clone())));
+    }
+    Ok(result)
+}
+
+// Helper function to get the result of an earlier upload `caller` made with the same idempotency key,
+// forgetting expired keys first
+fn remembered_upload(caller: Principal, key: &str, now: u64) -> Option<UploadResult> {
+    IDEMPOTENT_UPLOADS.with(|uploads| {
+        let mut uploads = uploads.borrow_mut();
+        uploads.retain(|_, (completed_at, _)| now.saturating_sub(*completed_at) < IDEMPOTENCY_TTL_NANOS);
+        uploads.get(&(caller, key.to_string())).map(|(_, result)| result.clone())
+    })
+}
+
+// Helper function to reject an upload once the caller has used up their allowance for the past hour
+fn check_upload_rate(caller: Principal, now: u64) -> Result<(), PlatformError> {
+    UPLOAD_HISTORY.with(|history| {
+        let mut history = history.borrow_mut();
+        let timestamps = history.entry(caller).or_default();
+        timestamps.retain(|timestamp| now.saturating_sub(*timestamp) < UPLOAD_RATE_WINDOW_NANOS);
+    });
+    upload_rate_allows(caller, now)
+}
+
+// Helper function to check the caller's upload allowance for the past hour without pruning their history
+fn upload_rate_allows(caller: Principal, now: u64) -> Result<(), PlatformError> {
+    let limit = DECENTRALIZED_PLATFORM.with(|platform| platform.borrow().max_uploads_per_hour());
+    UPLOAD_HISTORY.with(|history| {
+        let history = history.borrow();
+        let timestamps: Vec<u64> = history
+            .get(&caller)
+            .into_iter()
+            .flatten()
+            .copied()
+            .filter(|timestamp| now.saturating_sub(*timestamp) < UPLOAD_RATE_WINDOW_NANOS)
+            .collect();
+        if timestamps.len() < limit as usize {
+            return Ok(());
+        }
+
+        // A slot frees up when the oldest upload still counted leaves the window; with a zero limit none ever does
+        let wait_nanos = timestamps
+            .get(timestamps.len() - limit as usize)
+            .map_or(UPLOAD_RATE_WINDOW_NANOS, |oldest| {
+                (oldest + UPLOAD_RATE_WINDOW_NANOS).saturating_sub(now)
+            });
+        Err(PlatformError::RateLimited {
+            retry_after_secs: wait_nanos.div_ceil(1_000_000_000),
+        })
+    })
+}
+
+// Query function to check an upload before paying for the update call, returning every problem
+// `upload_property` would report for `owner` uploading a `image_len`-byte image, or nothing if it would go
+// through. Only the length is known here, so the image format and duplicate images aren't checked.
+#[query]
+fn validate_upload(
+    property_id: String,
+    image_len: u64,
+    property_type: PropertyType,
+    owner: Principal,
+) -> Vec<PlatformError> {
+    let now = env::time();
+    let property_id = normalize_id(&property_id);
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        let mut issues = Vec::new();
+        if platform.paused {
+            issues.push(PlatformError::Paused);
+        }
+        if owner == Principal::anonymous() || !platform.is_seller(owner) {
+            issues.push(PlatformError::Unauthorized);
+        }
+
+        if property_id.is_empty() {
+            issues.push(PlatformError::InvalidInput("The property ID is empty.".to_string()));
+        } else if property_id.len() > MAX_PROPERTY_ID_LEN {
+            issues.push(PlatformError::InvalidInput("The property_id is too long.".to_string()));
+        } else if platform.properties.contains_key(&property_id) || platform.trash.contains_key(&property_id) {
+            issues.push(PlatformError::DuplicateId { id: property_id });
+        }
+
+        match normalize_property_type(property_type) {
+            Ok(property_type) => {
+                if !platform.allows_type(&property_type) {
+                    let issue = "Uploads of this property type are disabled.".to_string();
+                    issues.push(PlatformError::InvalidInput(issue));
+                }
+                let label = type_label(&property_type);
+                if matches!(property_type, PropertyType::Other(_)) && label.len() > MAX_TYPE_LABEL_LEN {
+                    issues.push(PlatformError::InvalidInput("The property_type is too long.".to_string()));
+                }
+                if platform.contains_blocked_keyword(&label) {
+                    issues.push(PlatformError::InvalidInput("The listing contains a blocked keyword.".to_string()));
+                }
+                let limit_bytes = platform.max_image_bytes_for(&property_type);
+                if image_len > limit_bytes {
+                    issues.push(PlatformError::TooLarge { limit_bytes });
+                }
+            }
+            Err(issue) => issues.push(issue),
+        }
+        if image_len == 0 {
+            issues.push(PlatformError::EmptyImage);
+        }
+
+        if let Some(limit) = quota_reached(&platform, owner) {
+            issues.push(PlatformError::QuotaExceeded { limit });
+        }
+        if let Some(limit) = platform_full(&platform) {
+            issues.push(PlatformError::PlatformFull { limit });
+        }
+        if let Err(issue) = upload_rate_allows(owner, now) {
+            issues.push(issue);
+        }
+        issues
+    })
+}
+
+// Function to hand a property over to a new owner, only allowed for its current owner or an approved spender.
+// Returns a receipt, which is also appended to the property's transfer log.
+#[update]
+fn transfer_property(
+    property_id: String,
+    new_owner: Principal,
+    memo: Option<String>,
+) -> Result<TransferReceipt, PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let property_id = normalize_id(&property_id);
+    if memo.as_ref().is_some_and(|memo| memo.len() > MAX_MEMO_LEN) {
+        return Err(PlatformError::InvalidInput("The memo is too long.".to_string()));
+    }
+    ensure_no_sale_in_flight(&property_id)?;
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let quota = quota_reached(&platform, new_owner);
+        let cooldown_secs = platform.transfer_cooldown_secs;
+        let mut property = platform
+            .properties
+            .get_mut(&property_id)
+            .ok_or(PlatformError::NotFound)?;
+        let caller = env::caller();
+        if property.owner != caller && !property.approvals.contains(&caller) {
+            return Err(PlatformError::Unauthorized);
+        }
+        // Handing a property to its current owner doesn't add to their holdings
+        if let Some(limit) = quota.filter(|_| property.owner != new_owner) {
+            return Err(PlatformError::QuotaExceeded { limit });
+        }
+        let now = env::time();
+        if let Some(retry_after_secs) = cooldown_remaining(&property, cooldown_secs, now) {
+            if property.owner != new_owner {
+                return Err(PlatformError::CooldownActive { retry_after_secs });
+            }
+        }
+        let receipt = change_owner(&mut property, new_owner, memo, now);
+        property.commit();
+        platform.record_event(AuditEventKind::Transferred { to: new_owner }, &property_id);
+        Ok(receipt)
+    })
+}
+
+// Helper function to get how many seconds remain before a property may change owners again, if it is cooling down
+fn cooldown_remaining(property: &Property, cooldown_secs: Option<u64>, now: u64) -> Option<u64> {
+    let ready_at = property
+        .last_transfer_at()?
+        .saturating_add(cooldown_secs?.saturating_mul(1_000_000_000));
+    (now < ready_at).then(|| (ready_at - now).div_ceil(1_000_000_000))
+}
+
+// Function to move every live property the caller owns to another principal, e.g. when migrating accounts.
+// Returns how many were moved. Nothing moves if the recipient's quota can't take all of them, or if any of them
+// is still in its transfer cooldown; the error then gives the wait until the last of them is ready.
+#[update]
+fn transfer_all(to: Principal) -> Result<u64, PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let caller = env::caller();
+    if to == caller {
+        return Ok(0);
+    }
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let ids: Vec<String> = OWNER_INDEX.with(|index| {
+            let index = index.borrow();
+            let owned = index.owner_index.get(&caller).into_iter().flatten();
+            sorted_property_ids(owned).into_iter().cloned().collect()
+        });
+        for property_id in &ids {
+            ensure_no_sale_in_flight(property_id)?;
+        }
+        let now = env::time();
+        let cooldown_secs = platform.transfer_cooldown_secs;
+        let retry_after_secs = ids
+            .iter()
+            .filter_map(|property_id| {
+                let property = platform.properties.get(property_id).expect("Owner index is in sync");
+                cooldown_remaining(&property, cooldown_secs, now)
+            })
+            .max();
+        if let Some(retry_after_secs) = retry_after_secs {
+            return Err(PlatformError::CooldownActive { retry_after_secs });
+        }
+        if let Some(limit) = platform.max_properties_per_owner {
+            let held = OWNER_INDEX.with(|index| index.borrow().owner_index.get(&to).map_or(0, HashSet::len)) as u64;
+            if held + ids.len() as u64 > limit {
+                return Err(PlatformError::QuotaExceeded { limit });
+            }
+        }
+
+        for property_id in &ids {
+            let mut property = platform.properties.get_mut(property_id).expect("Owner index is in sync");
+            change_owner(&mut property, to, None, now);
+            property.commit();
+            platform.record_event(AuditEventKind::Transferred { to }, property_id);
+        }
+        Ok(ids.len() as u64)
+    })
+}
+
+// Function to let another principal, such as an escrow service, transfer a property; only allowed for its owner
+#[update]
+fn approve(property_id: String, spender: Principal) -> Result<(), PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let property_id = normalize_id(&property_id);
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let mut property = platform
+            .properties
+            .get_mut(&property_id)
+            .ok_or(PlatformError::NotFound)?;
+        if property.owner != env::caller() {
+            return Err(PlatformError::Unauthorized);
+        }
+        if property.approvals.contains(&spender) {
+            return Ok(());
+        }
+        if property.approvals.len() >= MAX_APPROVALS {
+            return Err(PlatformError::LimitReached(format!(
+                "A property can have at most {} approvals.",
+                MAX_APPROVALS
+            )));
+        }
+        property.approvals.push(spender);
+        property.updated_at = env::time();
+        property.commit();
+        platform.record_event(AuditEventKind::Approved { spender }, &property_id);
+        Ok(())
+    })
+}
+
+// Function to withdraw a previous approval, only allowed for the owner; revoking a missing approval is a no-op
+#[update]
+fn revoke_approval(property_id: String, spender: Principal) -> Result<(), PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let property_id = normalize_id(&property_id);
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let mut property = platform
+            .properties
+            .get_mut(&property_id)
+            .ok_or(PlatformError::NotFound)?;
+        if property.owner != env::caller() {
+            return Err(PlatformError::Unauthorized);
+        }
+        let before = property.approvals.len();
+        property.approvals.retain(|approved| *approved != spender);
+        if property.approvals.len() != before {
+            property.updated_at = env::time();
+            property.commit();
+            platform.record_event(AuditEventKind::ApprovalRevoked { spender }, &property_id);
+        }
+        Ok(())
+    })
+}
+
+// Function to agree to swap one of the caller's properties for another one, only allowed for its owner.
+// A property has at most one pending offer; approving again replaces it.
+#[update]
+fn approve_swap(my_property: String, their_property: String) -> Result<(), PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let my_property = normalize_id(&my_property);
+    let their_property = normalize_id(&their_property);
+    if my_property == their_property {
+        return Err(PlatformError::InvalidInput("A property can't be swapped for itself.".to_string()));
+    }
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let caller = env::caller();
+        let property = platform.properties.get(&my_property).ok_or(PlatformError::NotFound)?;
+        if property.owner != caller {
+            return Err(PlatformError::Unauthorized);
+        }
+        if !platform.properties.contains_key(&their_property) {
+            return Err(PlatformError::NotFound);
+        }
+        platform
+            .swap_approvals
+            .insert(my_property.clone(), (their_property.clone(), caller));
+        platform.record_event(
+            AuditEventKind::SwapApproved {
+                for_property: their_property,
+            },
+            &my_property,
+        );
+        Ok(())
+    })
+}
+
+// Function to exchange the caller's property for the counterparty's in one step, once both owners have
+// approved the swap. Either both ownership changes happen or, on any error, neither does.
+#[update]
+fn swap_properties(my_property: String, their_property: String, counterparty: Principal) -> Result<(), PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let my_property = normalize_id(&my_property);
+    let their_property = normalize_id(&their_property);
+    if my_property == their_property {
+        return Err(PlatformError::InvalidInput("A property can't be swapped for itself.".to_string()));
+    }
+    ensure_no_sale_in_flight(&my_property)?;
+    ensure_no_sale_in_flight(&their_property)?;
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let caller = env::caller();
+        let (Some(mine), Some(theirs)) = (
+            platform.properties.get(&my_property),
+            platform.properties.get(&their_property),
+        ) else {
+            return Err(PlatformError::NotFound);
+        };
+        if mine.owner != caller || theirs.owner != counterparty {
+            return Err(PlatformError::Unauthorized);
+        }
+        // A swap is held to the transfer cooldown like any other handover, so it can't move a property on early.
+        // Quotas don't come into it: each owner gives up one property for the one they get.
+        let now = env::time();
+        let cooldown_secs = platform.transfer_cooldown_secs;
+        let retry_after_secs = [&mine, &theirs]
+            .into_iter()
+            .filter_map(|property| cooldown_remaining(property, cooldown_secs, now))
+            .max();
+        if let Some(retry_after_secs) = retry_after_secs {
+            return Err(PlatformError::CooldownActive { retry_after_secs });
+        }
+        // Approvals are tied to the principal who gave them, so they lapse if either property changes hands
+        let approved = |property_id: &String, other: &String, owner: Principal| {
+            platform.swap_approvals.get(property_id) == Some(&(other.clone(), owner))
+        };
+        if !approved(&my_property, &their_property, caller) || !approved(&their_property, &my_property, counterparty) {
+            return Err(PlatformError::Conflict("Both owners have to approve this swap first.".to_string()));
+        }
+
+        platform.swap_approvals.remove(&my_property);
+        platform.swap_approvals.remove(&their_property);
+        for (property_id, other, new_owner) in [
+            (&my_property, &their_property, counterparty),
+            (&their_property, &my_property, caller),
+        ] {
+            let mut property = platform
+                .properties
+                .get_mut(property_id)
+                .expect("Both properties were checked above");
+            change_owner(&mut property, new_owner, Some(format!("Swapped for {}", other)), now);
+            property.commit();
+            platform.record_event(AuditEventKind::Transferred { to: new_owner }, property_id);
+        }
+        Ok(())
+    })
+}
+
+// Function to make a listing public or private with the given allow-list, only allowed for the owner
+#[update]
+fn set_visibility(property_id: String, visibility: Visibility) -> Result<(), PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let property_id = normalize_id(&property_id);
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let mut property = platform
+            .properties
+            .get_mut(&property_id)
+            .ok_or(PlatformError::NotFound)?;
+        if property.owner != env::caller() {
+            return Err(PlatformError::Unauthorized);
+        }
+        let visibility = match visibility {
+            Visibility::Private(mut allowed) => {
+                allowed.sort();
+                allowed.dedup();
+                if allowed.len() > MAX_ACCESS_GRANTS {
+                    return Err(PlatformError::LimitReached(format!(
+                        "A private listing can be shared with at most {} principals.",
+                        MAX_ACCESS_GRANTS
+                    )));
+                }
+                Visibility::Private(allowed)
+            }
+            Visibility::Public => Visibility::Public,
+        };
+        let public = visibility == Visibility::Public;
+        property.visibility = visibility;
+        property.updated_at = env::time();
+        property.commit();
+        platform.record_event(AuditEventKind::VisibilityChanged { public }, &property_id);
+        Ok(())
+    })
+}
+
+// Function to share a listing with a principal, only allowed for the owner; a public listing becomes private
+#[update]
+fn grant_access(property_id: String, grantee: Principal) -> Result<(), PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let property_id = normalize_id(&property_id);
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let mut property = platform
+            .properties
+            .get_mut(&property_id)
+            .ok_or(PlatformError::NotFound)?;
+        if property.owner != env::caller() {
+            return Err(PlatformError::Unauthorized);
+        }
+        if let Visibility::Private(allowed) = &property.visibility {
+            if allowed.contains(&grantee) {
+                return Ok(());
+            }
+            if allowed.len() >= MAX_ACCESS_GRANTS {
+                return Err(PlatformError::LimitReached(format!(
+                    "A private listing can be shared with at most {} principals.",
+                    MAX_ACCESS_GRANTS
+                )));
+            }
+        }
+        match &mut property.visibility {
+            Visibility::Private(allowed) => allowed.push(grantee),
+            public => *public = Visibility::Private(vec![grantee]),
+        }
+        property.updated_at = env::time();
+        property.commit();
+        platform.record_event(AuditEventKind::AccessGranted { grantee }, &property_id);
+        Ok(())
+    })
+}
+
+// Function to stop sharing a private listing with a principal, only allowed for the owner.
+// The listing stays private even once its allow-list is empty; revoking a missing grant is a no-op.
+#[update]
+fn revoke_access(property_id: String, grantee: Principal) -> Result<(), PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let property_id = normalize_id(&property_id);
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let mut property = platform
+            .properties
+            .get_mut(&property_id)
+            .ok_or(PlatformError::NotFound)?;
+        if property.owner != env::caller() {
+            return Err(PlatformError::Unauthorized);
+        }
+        let Visibility::Private(allowed) = &mut property.visibility else {
+            return Ok(());
+        };
+        let before = allowed.len();
+        allowed.retain(|allowed| *allowed != grantee);
+        if allowed.len() != before {
+            property.updated_at = env::time();
+            property.commit();
+            platform.record_event(AuditEventKind::AccessRevoked { grantee }, &property_id);
+        }
+        Ok(())
+    })
+}
+
+// Function to move some of the caller's shares in a property to another principal
+#[update]
+fn transfer_shares(property_id: String, to: Principal, amount: u64) -> Result<(), PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let property_id = normalize_id(&property_id);
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let mut property = platform
+            .properties
+            .get_mut(&property_id)
+            .ok_or(PlatformError::NotFound)?;
+        let caller = env::caller();
+        let held = property.shares.get(&caller).copied().unwrap_or(0);
+        if amount > held {
+            return Err(PlatformError::Conflict("The caller doesn't hold that many shares.".to_string()));
+        }
+        if amount == 0 || to == caller {
+            return Ok(());
+        }
+        let received = property
+            .shares
+            .get(&to)
+            .copied()
+            .unwrap_or(0)
+            .checked_add(amount)
+            .ok_or(PlatformError::Overflow)?;
+
+        if held == amount {
+            property.shares.remove(&caller);
+        } else {
+            property.shares.insert(caller, held - amount);
+        }
+        property.shares.insert(to, received);
+        assert_eq!(
+            property.shares.values().try_fold(0u64, |total, shares| total.checked_add(*shares)),
+            Some(TOTAL_SHARES),
+            "Shares of a property must always add up to the total"
+        );
+        property.updated_at = env::time();
+        property.commit();
+        platform.record_event(AuditEventKind::SharesTransferred { to, amount }, &property_id);
+        Ok(())
+    })
+}
+
+// Query function to list each co-owner of a property with the basis points they hold
+#[query]
+fn get_shareholders(property_id: String) -> Vec<(Principal, u64)> {
+    let property_id = normalize_id(&property_id);
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        platform
+            .properties
+            .get(&property_id)
+            .map(|property| property.shares.iter().map(|(holder, shares)| (*holder, *shares)).collect())
+            .unwrap_or_default()
+    })
+}
+
+// Query function to list the principals currently approved to transfer a property
+#[query]
+fn get_approvals(property_id: String) -> Vec<Principal> {
+    let property_id = normalize_id(&property_id);
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        platform
+            .properties
+            .get(&property_id)
+            .map(|property| property.approvals.clone())
+            .unwrap_or_default()
+    })
+}
+
+// Helper function shared by transfers and sales to hand a property to its new owner, logging a receipt
+fn change_owner(property: &mut Property, new_owner: Principal, memo: Option<String>, now: u64) -> TransferReceipt {
+    property.updated_at = now;
+    // Any hold, approval, or auction set up by the previous owner no longer applies
+    if property.reservation.take().is_some() {
+        property.status = ListingStatus::Available;
+    }
+    property.auction = None;
+    property.approvals.clear();
+    // The outgoing owner's stake goes with the property; other co-owners keep theirs
+    let previous_owner = std::mem::replace(&mut property.owner, new_owner);
+    if let Some(stake) = property.shares.remove(&previous_owner) {
+        // Stakes sum to TOTAL_SHARES, so this can't actually saturate
+        let held = property.shares.entry(new_owner).or_default();
+        *held = held.saturating_add(stake);
+    }
+    property.owner_history.push(OwnershipRecord {
+        owner: new_owner,
+        acquired_at: now,
+    });
+    let receipt = TransferReceipt {
+        property_id: property.id.clone(),
+        from: previous_owner,
+        to: new_owner,
+        at: now,
+        memo,
+    };
+    property.transfers.push(receipt.clone());
+    receipt
+}
+
+// Helper function to build the bytes an owner signs: the property ID, primary image hash, and description,
+// separated by newlines. The description comes last so it may contain newlines itself.
+fn signed_payload(property: &Property) -> Vec<u8> {
+    let primary_hash = property.image_hashes.first().map(String::as_str).unwrap_or_default();
+    format!("{}\n{}\n{}", property.id, primary_hash, property.description).into_bytes()
+}
+
+// Helper function to check an Ed25519 signature over `message`; malformed keys or signatures simply fail.
+// `verify_strict` also rejects small-order keys and non-canonical encodings, so a signature can't be reshaped.
+fn verify_ed25519(public_key: &[u8], message: &[u8], signature: &[u8]) -> bool {
+    let Ok(public_key) = <[u8; 32]>::try_from(public_key) else {
+        return false;
+    };
+    let Ok(signature) = ed25519_dalek::Signature::from_slice(signature) else {
+        return false;
+    };
+    ed25519_dalek::VerifyingKey::from_bytes(&public_key)
+        .is_ok_and(|public_key| public_key.verify_strict(message, &signature).is_ok())
+}
+
+// Function to attach the owner's Ed25519 signature over the property's metadata, only allowed for its owner.
+// The signature has to verify against the current record; any later change to the signed fields breaks it.
+#[update]
+fn sign_metadata(property_id: String, pubkey: Vec<u8>, signature: Vec<u8>) -> Result<(), PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let property_id = normalize_id(&property_id);
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let mut property = platform
+            .properties
+            .get_mut(&property_id)
+            .ok_or(PlatformError::NotFound)?;
+        if property.owner != env::caller() {
+            return Err(PlatformError::Unauthorized);
+        }
+        if !verify_ed25519(&pubkey, &signed_payload(&property), &signature) {
+            return Err(PlatformError::InvalidInput(
+                "The signature doesn't verify against the public key.".to_string(),
+            ));
+        }
+        property.signature = Some(signature);
+        property.pubkey = Some(pubkey);
+        property.updated_at = env::time();
+        property.commit();
+        platform.record_event(AuditEventKind::MetadataSigned, &property_id);
+        Ok(())
+    })
+}
+
+// Query function to check a property's stored signature against its current metadata; unsigned
+// or missing properties report false
+#[query]
+fn verify_metadata_signature(property_id: String) -> bool {
+    let property_id = normalize_id(&property_id);
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        let Some(property) = platform.properties.get(&property_id) else {
+            return false;
+        };
+        match (&property.pubkey, &property.signature) {
+            (Some(pubkey), Some(signature)) => verify_ed25519(pubkey, &signed_payload(&property), signature),
+            _ => false,
+        }
+    })
+}
+
+// Function to replace a property's description, only allowed for its owner
+#[update]
+fn update_description(property_id: String, new_description: String) -> Result<(), PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let property_id = normalize_id(&property_id);
+    if new_description.len() > MAX_DESCRIPTION_LEN {
+        return Err(PlatformError::InvalidInput("The description is too long.".to_string()));
+    }
+
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        if platform.contains_blocked_keyword(&new_description) {
+            return Err(PlatformError::InvalidInput("The description contains a blocked keyword.".to_string()));
+        }
+        let mut property = platform
+            .properties
+            .get_mut(&property_id)
+            .ok_or(PlatformError::NotFound)?;
+        if property.owner != env::caller() {
+            return Err(PlatformError::Unauthorized);
+        }
+        property.description = new_description;
+        property.updated_at = env::time();
+        property.commit();
+        platform.record_event(AuditEventKind::DescriptionUpdated, &property_id);
+        Ok(())
+    })
+}
+
+// Function to add text to the end of a property's description, only allowed for its owner. A blank line
+// separates it from any existing text, and the result must stay within MAX_DESCRIPTION_LEN.
+#[update]
+fn append_to_description(property_id: String, suffix: String) -> Result<(), PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let property_id = normalize_id(&property_id);
+    if suffix.trim().is_empty() {
+        return Err(PlatformError::InvalidInput("There is nothing to append.".to_string()));
+    }
+
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let property = platform.properties.get(&property_id).ok_or(PlatformError::NotFound)?;
+        if property.owner != env::caller() {
+            return Err(PlatformError::Unauthorized);
+        }
+        let description = if property.description.is_empty() {
+            suffix
+        } else {
+            format!("{}\n\n{}", property.description, suffix)
+        };
+        if description.len() > MAX_DESCRIPTION_LEN {
+            return Err(PlatformError::InvalidInput("The description is too long.".to_string()));
+        }
+        // A blocked phrase could span the old text and the new, so the whole result is checked
+        if platform.contains_blocked_keyword(&description) {
+            return Err(PlatformError::InvalidInput("The description contains a blocked keyword.".to_string()));
+        }
+        let mut property = platform.properties.get_mut(&property_id).expect("Property was found above");
+        property.description = description;
+        property.updated_at = env::time();
+        property.commit();
+        platform.record_event(AuditEventKind::DescriptionUpdated, &property_id);
+        Ok(())
+    })
+}
+
+// Function to append another image to a property, only allowed for its owner
+#[update]
+fn add_image(property_id: String, image_data: Vec<u8>) -> Result<String, PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let property_id = normalize_id(&property_id);
+    if image_data.is_empty() {
+        return Err(PlatformError::EmptyImage);
+    }
+
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let (max_image_bytes, encrypted) = platform
+            .properties
+            .get(&property_id)
+            .map(|property| (platform.max_image_bytes_for(&property.property_type), property.encrypted))
+            .ok_or(PlatformError::NotFound)?;
+        if image_data.len() as u64 > max_image_bytes {
+            return Err(PlatformError::TooLarge {
+                limit_bytes: max_image_bytes,
+            });
+        }
+        if !encrypted && detect_format(&image_data).is_none() {
+            return Err(PlatformError::InvalidInput("The image format is not supported.".to_string()));
+        }
+        let salt = platform.hash_salt().to_vec();
+        let property = platform
+            .properties
+            .get(&property_id)
+            .ok_or(PlatformError::NotFound)?;
+        if property.owner != env::caller() {
+            return Err(PlatformError::Unauthorized);
+        }
+
+        let hash = hash_image(&image_data, property.hash_algo, &salt);
+        if !property.image_hashes.contains(&hash) {
+            let image_len = image_data.len() as u64;
+            platform.retain_image(hash.clone(), image_data)?;
+            let mut property = platform.properties.get_mut(&property_id).expect("Property checked above");
+            property.image_hashes.push(hash.clone());
+            property.image_size_bytes = property.image_size_bytes.saturating_add(image_len);
+            property.updated_at = env::time();
+            property.commit();
+            platform.record_event(AuditEventKind::ImageAdded { hash: hash.clone() }, &property_id);
+        }
+        Ok(hash)
+    })
+}
+
+// Function to remove one image from a property, only allowed for its owner
+#[update]
+fn remove_image(property_id: String, hash: String) -> Result<(), PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let property_id = normalize_id(&property_id);
+    let hash = parse_hash(&hash)?;
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let removed_len = platform.original_image_len(&hash).unwrap_or(0);
+        let mut property = platform
+            .properties
+            .get(&property_id)
+            .ok_or(PlatformError::NotFound)?;
+        if property.owner != env::caller() {
+            return Err(PlatformError::Unauthorized);
+        }
+        let position = property
+            .image_hashes
+            .iter()
+            .position(|existing| *existing == hash)
+            .ok_or(PlatformError::NotFound)?;
+        if property.image_hashes.len() == 1 {
+            return Err(PlatformError::Conflict("A property must keep at least one image.".to_string()));
+        }
+
+        property.image_hashes.remove(position);
+        property.image_size_bytes = property.image_size_bytes.saturating_sub(removed_len);
+        property.updated_at = env::time();
+        // Removing the primary image promotes the next one, so its format has to follow. It is read from the
+        // original bytes, since the stored ones may be compressed.
+        if position == 0 && !property.encrypted {
+            property.format = platform
+                .image_data(&property.image_hashes[0])
+                .and_then(|image_data| detect_format(&image_data));
+        }
+        platform.properties.insert(property_id.clone(), property);
+        platform.release_image(&hash);
+        platform.record_event(AuditEventKind::ImageRemoved { hash }, &property_id);
+        Ok(())
+    })
+}
+
+// Function to set or replace one attribute of a property, only allowed for its owner
+#[update]
+fn set_attribute(property_id: String, key: String, value: String) -> Result<(), PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let property_id = normalize_id(&property_id);
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let mut property = platform
+            .properties
+            .get_mut(&property_id)
+            .ok_or(PlatformError::NotFound)?;
+        if property.owner != env::caller() {
+            return Err(PlatformError::Unauthorized);
+        }
+
+        let previous = property.attributes.insert(key.clone(), value);
+        if !attributes_within_limits(&property.attributes) {
+            // Put the old value back so a rejected edit leaves the property untouched
+            match previous {
+                Some(previous) => property.attributes.insert(key, previous),
+                None => property.attributes.remove(&key),
+            };
+            return Err(PlatformError::LimitReached(format!(
+                "A property can have at most {} attributes totalling {} bytes.",
+                MAX_ATTRIBUTES, MAX_ATTRIBUTES_BYTES
+            )));
+        }
+        property.updated_at = env::time();
+        property.commit();
+        platform.record_event(AuditEventKind::AttributeSet { key }, &property_id);
+        Ok(())
+    })
+}
+
+// Function to remove one attribute from a property, only allowed for its owner
+#[update]
+fn remove_attribute(property_id: String, key: String) -> Result<(), PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let property_id = normalize_id(&property_id);
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let mut property = platform
+            .properties
+            .get_mut(&property_id)
+            .ok_or(PlatformError::NotFound)?;
+        if property.owner != env::caller() {
+            return Err(PlatformError::Unauthorized);
+        }
+        if property.attributes.remove(&key).is_none() {
+            return Err(PlatformError::NotFound);
+        }
+        property.updated_at = env::time();
+        property.commit();
+        platform.record_event(AuditEventKind::AttributeRemoved { key }, &property_id);
+        Ok(())
+    })
+}
+
+// Function to set or clear a property's location, only allowed for its owner
+#[update]
+fn set_location(property_id: String, location: Option<GeoLocation>) -> Result<(), PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let property_id = normalize_id(&property_id);
+    if location.as_ref().is_some_and(|location| !location.is_valid()) {
+        return Err(PlatformError::InvalidInput("The location is out of range.".to_string()));
+    }
+
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let mut property = platform
+            .properties
+            .get_mut(&property_id)
+            .ok_or(PlatformError::NotFound)?;
+        if property.owner != env::caller() {
+            return Err(PlatformError::Unauthorized);
+        }
+        property.location = location;
+        property.updated_at = env::time();
+        property.commit();
+        platform.record_event(AuditEventKind::LocationUpdated, &property_id);
+        Ok(())
+    })
+}
+
+// Function to tag a property, only allowed for its owner; adding a tag it already has is a no-op
+#[update]
+fn add_tag(property_id: String, tag: String) -> Result<(), PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let property_id = normalize_id(&property_id);
+    let tag = normalize_tag(&tag);
+    if tag.is_empty() {
+        return Err(PlatformError::InvalidInput("Tags must contain at least one letter or digit.".to_string()));
+    }
+
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let mut property = platform
+            .properties
+            .get_mut(&property_id)
+            .ok_or(PlatformError::NotFound)?;
+        if property.owner != env::caller() {
+            return Err(PlatformError::Unauthorized);
+        }
+        if property.tags.contains(&tag) {
+            return Ok(());
+        }
+        if property.tags.len() >= MAX_TAGS {
+            return Err(PlatformError::LimitReached(format!("A property can have at most {} tags.", MAX_TAGS)));
+        }
+        property.tags.push(tag.clone());
+        property.updated_at = env::time();
+        property.commit();
+        platform.record_event(AuditEventKind::TagAdded { tag }, &property_id);
+        Ok(())
+    })
+}
+
+// Function to remove a tag from a property, only allowed for its owner
+#[update]
+fn remove_tag(property_id: String, tag: String) -> Result<(), PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let property_id = normalize_id(&property_id);
+    let tag = normalize_tag(&tag);
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let mut property = platform
+            .properties
+            .get_mut(&property_id)
+            .ok_or(PlatformError::NotFound)?;
+        if property.owner != env::caller() {
+            return Err(PlatformError::Unauthorized);
+        }
+        let position = property
+            .tags
+            .iter()
+            .position(|existing| *existing == tag)
+            .ok_or(PlatformError::NotFound)?;
+        property.tags.remove(position);
+        property.updated_at = env::time();
+        property.commit();
+        platform.record_event(AuditEventKind::TagRemoved { tag }, &property_id);
+        Ok(())
+    })
+}
+
+// Helper function to bring a tag into its stored form so lookups are case-insensitive
+fn normalize_tag(tag: &str) -> String {
+    tag.trim().to_lowercase()
+}
+
+// Helper function to bring a property ID into its stored form, so "P1", "p1", and " p1 " name the same record
+fn normalize_id(property_id: &str) -> String {
+    property_id.trim().to_lowercase()
+}
+
+// Helper function to re-key records saved before IDs were normalized.
+// An ID whose normalized form is already taken keeps its original key and is left for the admin to resolve.
+fn normalize_stored_ids(platform: &mut DecentralizedPlatform) {
+    let stale: Vec<String> = platform
+        .properties
+        .keys()
+        .chain(platform.trash.keys().cloned())
+        .filter(|id| normalize_id(id) != *id)
+        .collect();
+    for id in stale {
+        let normalized = normalize_id(&id);
+        if normalized.is_empty() || platform.properties.contains_key(&normalized) || platform.trash.contains_key(&normalized) {
+            continue;
+        }
+        if let Some(mut property) = platform.properties.remove(&id) {
+            property.id = normalized.clone();
+            platform.properties.insert(normalized.clone(), property);
+        } else {
+            let mut property = platform.trash.remove(&id).expect("Property listed above");
+            property.id = normalized.clone();
+            platform.trash.insert(normalized.clone(), property);
+        }
+        for favorites in platform.favorites.values_mut() {
+            if favorites.remove(&id) {
+                favorites.insert(normalized.clone());
+            }
+        }
+    }
+}
+
+// Helper function to canonicalize a property type so equivalent `Other` labels land in one category
+fn normalize_property_type(property_type: PropertyType) -> Result<PropertyType, PlatformError> {
+    let PropertyType::Other(label) = property_type else {
+        return Ok(property_type);
+    };
+    let label = label.trim().to_lowercase();
+    // Collapse well-known synonyms onto the built-in variants
+    match label.as_str() {
+        "" => Err(PlatformError::InvalidInput("The property type label is empty.".to_string())),
+        "car" | "cars" | "auto" | "automobile" | "vehicle" => Ok(PropertyType::Car),
+        "art" | "artwork" | "painting" | "sculpture" => Ok(PropertyType::Art),
+        "realestate" | "real estate" | "real_estate" | "real-estate" | "house" | "apartment" | "land" => {
+            Ok(PropertyType::RealEstate)
+        }
+        _ => Ok(PropertyType::Other(label)),
+    }
+}
+
+// Helper function to check a property's attributes against the count and size caps
+fn attributes_within_limits(attributes: &BTreeMap<String, String>) -> bool {
+    let total_bytes: usize = attributes.iter().map(|(key, value)| key.len() + value.len()).sum();
+    attributes.len() <= MAX_ATTRIBUTES && total_bytes <= MAX_ATTRIBUTES_BYTES
+}
+
+// Function to set or clear when a listing expires, only allowed for its owner
+#[update]
+fn set_expiry(property_id: String, expires_at: Option<u64>) -> Result<(), PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let property_id = normalize_id(&property_id);
+    let now = env::time();
+    if expires_at.is_some_and(|expires_at| expires_at <= now) {
+        return Err(PlatformError::InvalidInput("The expiry must be in the future.".to_string()));
+    }
+
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let mut property = platform
+            .properties
+            .get_mut(&property_id)
+            .ok_or(PlatformError::NotFound)?;
+        if property.owner != env::caller() {
+            return Err(PlatformError::Unauthorized);
+        }
+        property.expires_at = expires_at;
+        property.updated_at = now;
+        property.commit();
+        platform.record_event(AuditEventKind::ExpiryUpdated { expires_at }, &property_id);
+        Ok(())
+    })
+}
+
+// Function to move a listing through the sale process, only allowed for its owner
+#[update]
+fn set_status(property_id: String, status: ListingStatus) -> Result<(), PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let property_id = normalize_id(&property_id);
+    ensure_no_sale_in_flight(&property_id)?;
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let mut property = platform
+            .properties
+            .get_mut(&property_id)
+            .ok_or(PlatformError::NotFound)?;
+        if property.owner != env::caller() {
+            return Err(PlatformError::Unauthorized);
+        }
+        property.status = status;
+        if status != ListingStatus::Pending {
+            property.reservation = None;
+        }
+        property.updated_at = env::time();
+        property.commit();
+        platform.record_event(AuditEventKind::StatusChanged { status }, &property_id);
+        Ok(())
+    })
+}
+
+// Function to place a temporary hold on an available listing for the caller
+#[update]
+fn reserve_property(property_id: String) -> Result<(), PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let property_id = normalize_id(&property_id);
+    let caller = env::caller();
+    let now = env::time();
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let mut property = platform
+            .properties
+            .get_mut(&property_id)
+            .ok_or(PlatformError::NotFound)?;
+        if property.owner == caller {
+            return Err(PlatformError::Unauthorized);
+        }
+        let released = property.expire_reservation(now);
+        if property.status != ListingStatus::Available || property.has_expired(now) || property.auction.is_some() {
+            return Err(PlatformError::NotAvailable);
+        }
+
+        let expires_at = now.saturating_add(RESERVATION_TTL_NANOS);
+        property.status = ListingStatus::Pending;
+        property.reservation = Some(Reservation {
+            reserved_by: caller,
+            expires_at,
+        });
+        property.updated_at = now;
+        property.commit();
+        if released {
+            platform.record_event(AuditEventKind::ReservationExpired, &property_id);
+        }
+        platform.record_event(AuditEventKind::Reserved { expires_at }, &property_id);
+        Ok(())
+    })
+}
+
+// Function to release a hold, callable by the reserving buyer or the owner
+#[update]
+fn cancel_reservation(property_id: String) -> Result<(), PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let property_id = normalize_id(&property_id);
+    let caller = env::caller();
+    let now = env::time();
+    ensure_no_sale_in_flight(&property_id)?;
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let mut property = platform
+            .properties
+            .get_mut(&property_id)
+            .ok_or(PlatformError::NotFound)?;
+        let reserved_by = match &property.reservation {
+            Some(reservation) if reservation.expires_at > now => reservation.reserved_by,
+            _ => return Err(PlatformError::Conflict("The listing isn't reserved.".to_string())),
+        };
+        if caller != reserved_by && caller != property.owner {
+            return Err(PlatformError::Unauthorized);
+        }
+
+        property.reservation = None;
+        property.status = ListingStatus::Available;
+        property.updated_at = now;
+        property.commit();
+        platform.record_event(AuditEventKind::ReservationCancelled, &property_id);
+        Ok(())
+    })
+}
+
+// Function to rate a listing; each principal may review a property once, and never their own
+#[update]
+fn add_review(property_id: String, stars: u8, comment: String) -> Result<(), PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let property_id = normalize_id(&property_id);
+    if !(1..=5).contains(&stars) {
+        return Err(PlatformError::InvalidInput("Ratings must be between 1 and 5 stars.".to_string()));
+    }
+    if comment.len() > MAX_REVIEW_COMMENT_LEN {
+        return Err(PlatformError::InvalidInput("The comment is too long.".to_string()));
+    }
+
+    let reviewer = env::caller();
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let mut property = platform
+            .properties
+            .get_mut(&property_id)
+            .ok_or(PlatformError::NotFound)?;
+        if property.owner == reviewer {
+            return Err(PlatformError::Conflict("Owners can't review their own property.".to_string()));
+        }
+        if property.reviews.iter().any(|review| review.reviewer == reviewer) {
+            return Err(PlatformError::Conflict("The caller has already reviewed this property.".to_string()));
+        }
+        property.reviews.push(Review {
+            reviewer,
+            stars,
+            comment,
+            created_at: env::time(),
+        });
+        property.commit();
+        platform.record_event(AuditEventKind::ReviewAdded { stars }, &property_id);
+        Ok(())
+    })
+}
+
+// Query function to get a property's mean star rating, if it exists and has been reviewed
+#[query]
+fn get_average_rating(property_id: String) -> Option<f64> {
+    let property_id = normalize_id(&property_id);
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        let reviews = &platform.properties.get(&property_id)?.reviews;
+        if reviews.is_empty() {
+            return None;
+        }
+        let total: u64 = reviews.iter().map(|review| u64::from(review.stars)).sum();
+        Some(total as f64 / reviews.len() as f64)
+    })
+}
+
+// Function to put an available listing up for auction until `ends_at`, only allowed for its owner
+#[update]
+fn start_auction(property_id: String, min_bid_e8s: u64, ends_at: u64) -> Result<(), PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let property_id = normalize_id(&property_id);
+    let now = env::time();
+    if ends_at <= now {
+        return Err(PlatformError::InvalidInput("The auction must end in the future.".to_string()));
+    }
+
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let mut property = platform
+            .properties
+            .get_mut(&property_id)
+            .ok_or(PlatformError::NotFound)?;
+        if property.owner != env::caller() {
+            return Err(PlatformError::Unauthorized);
+        }
+        let released = property.expire_reservation(now);
+        if property.status != ListingStatus::Available || property.has_expired(now) || property.auction.is_some() {
+            return Err(PlatformError::NotAvailable);
+        }
+
+        property.auction = Some(Auction {
+            min_bid_e8s,
+            ends_at,
+            bids: Vec::new(),
+        });
+        property.updated_at = now;
+        property.commit();
+        if released {
+            platform.record_event(AuditEventKind::ReservationExpired, &property_id);
+        }
+        platform.record_event(AuditEventKind::AuctionStarted { min_bid_e8s, ends_at }, &property_id);
+        Ok(())
+    })
+}
+
+// Function to bid on a running auction; a bid must reach the minimum and beat the current highest bid
+#[update]
+fn place_bid(property_id: String, amount_e8s: u64) -> Result<(), PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let property_id = normalize_id(&property_id);
+    let bidder = env::caller();
+    let now = env::time();
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let mut property = platform
+            .properties
+            .get_mut(&property_id)
+            .ok_or(PlatformError::NotFound)?;
+        if property.owner == bidder {
+            return Err(PlatformError::Unauthorized);
+        }
+        let auction = property
+            .auction
+            .as_mut()
+            .ok_or_else(|| PlatformError::Conflict("No auction is running.".to_string()))?;
+        if now >= auction.ends_at {
+            return Err(PlatformError::Conflict("The auction has ended.".to_string()));
+        }
+        let minimum_e8s = match auction.bids.last() {
+            // Nothing can beat a bid of u64::MAX, so it can't just be matched either
+            Some(highest) => highest.amount_e8s.checked_add(1).ok_or(PlatformError::Overflow)?,
+            None => auction.min_bid_e8s,
+        };
+        if amount_e8s < minimum_e8s {
+            return Err(PlatformError::BidTooLow { minimum_e8s });
+        }
+        if auction.bids.len() >= MAX_BIDS_PER_AUCTION {
+            return Err(PlatformError::LimitReached(format!(
+                "An auction can take at most {} bids.",
+                MAX_BIDS_PER_AUCTION
+            )));
+        }
+
+        auction.bids.push(Bid {
+            bidder,
+            amount_e8s,
+            placed_at: now,
+        });
+        property.updated_at = now;
+        property.commit();
+        platform.record_event(AuditEventKind::BidPlaced { amount_e8s }, &property_id);
+        Ok(())
+    })
+}
+
+// Function to close an auction once it has ended, selling the property to the highest bidder who can pay.
+// Bids are only promises, so the winning amount is pulled from the bidder through the ledger (ICRC-2, so bidders
+// must approve this canister) and paid out to the seller and creator just like `buy_property`. A bidder who can't
+// pay or is at their property quota is passed over for the next highest; with nobody left, the listing is simply
+// available again. Anyone may call it, since the outcome is fixed by then.
+#[update]
+async fn settle_auction(property_id: String) -> Result<(), PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let property_id = normalize_id(&property_id);
+    let now = env::time();
+    // Also stops a second settlement from charging another bidder while the first is collecting
+    ensure_no_sale_in_flight(&property_id)?;
+    let (seller, creator, royalty_bps, bids, ledger) = with_state(|platform| {
+        let property = platform.properties.get(&property_id).ok_or(PlatformError::NotFound)?;
+        let auction = property
+            .auction
+            .as_ref()
+            .ok_or_else(|| PlatformError::Conflict("No auction is running.".to_string()))?;
+        if now < auction.ends_at {
+            return Err(PlatformError::Conflict("The auction hasn't ended yet.".to_string()));
+        }
+        // The creator's cut of a resale; a creator selling their own property is paid in full as the seller
+        let royalty_bps = if property.creator == property.owner { 0 } else { property.royalty_bps };
+        Ok((property.owner, property.creator, royalty_bps, auction.bids.clone(), platform.ledger_canister()))
+    })?;
+
+    SALES_IN_FLIGHT.with(|sales| sales.borrow_mut().insert(property_id.clone(), now));
+    let winning_bid = collect_winning_bid(&property_id, seller, &bids, royalty_bps, ledger).await;
+    SALES_IN_FLIGHT.with(|sales| sales.borrow_mut().remove(&property_id));
+
+    if let Some((royalty_e8s, seller_e8s)) = winning_bid? {
+        pay_from_escrow(ledger, seller, seller_e8s).await;
+        if royalty_e8s > 0 {
+            pay_from_escrow(ledger, creator, royalty_e8s).await;
+        }
+    }
+    Ok(())
+}
+
+// Helper function to take payment from the highest bidder who can pay and close the auction in their favour,
+// returning the royalty and seller shares of what they paid. With no such bidder, the auction closes unsold.
+async fn collect_winning_bid(
+    property_id: &str,
+    seller: Principal,
+    bids: &[Bid],
+    royalty_bps: u16,
+    ledger: Principal,
+) -> Result<Option<(u64, u64)>, PlatformError> {
+    let mut passed_over = HashSet::new();
+    // Bids only ever go up, so the newest is the highest; each bidder is tried once, at their highest bid
+    for bid in bids.iter().rev() {
+        if !passed_over.insert(bid.bidder) || with_state(|platform| quota_reached(platform, bid.bidder)).is_some() {
+            continue;
+        }
+        let split = royalty_split(bid.amount_e8s, royalty_bps)?;
+        if pull_payment(ledger, bid.bidder, env::id(), bid.amount_e8s).await.is_err() {
+            continue;
+        }
+        match with_state_mut(|platform| close_auction(platform, property_id, seller, Some(bid.bidder))) {
+            Ok(true) => return Ok(Some(split)),
+            // The bidder reached their quota while paying, so they get their money back and the next bid is tried
+            Ok(false) => pay_from_escrow(ledger, bid.bidder, bid.amount_e8s).await,
+            Err(error) => {
+                pay_from_escrow(ledger, bid.bidder, bid.amount_e8s).await;
+                return Err(error);
+            }
+        }
+    }
+    with_state_mut(|platform| close_auction(platform, property_id, seller, None))?;
+    Ok(None)
+}
+
+// Helper function to end a property's auction, handing it to `winner` if there is one. Returns false, leaving the
+// auction running, if the winner has reached their property quota; fails if the auction is no longer there to end.
+fn close_auction(
+    platform: &mut DecentralizedPlatform,
+    property_id: &str,
+    seller: Principal,
+    winner: Option<Principal>,
+) -> Result<bool, PlatformError> {
+    let quota = winner.and_then(|winner| quota_reached(platform, winner));
+    let mut property = platform.properties.get_mut(property_id).ok_or(PlatformError::SaleInterrupted)?;
+    if property.owner != seller || property.auction.is_none() {
+        return Err(PlatformError::SaleInterrupted);
+    }
+    if quota.is_some() {
+        return Ok(false);
+    }
+
+    let now = env::time();
+    property.auction = None;
+    property.updated_at = now;
+    if let Some(winner) = winner {
+        change_owner(&mut property, winner, None, now);
+        property.status = ListingStatus::Sold;
+    }
+    property.commit();
+    platform.record_event(AuditEventKind::AuctionSettled { winner }, property_id);
+    if let Some(winner) = winner {
+        platform.record_event(AuditEventKind::Transferred { to: winner }, property_id);
+        platform.record_event(AuditEventKind::StatusChanged { status: ListingStatus::Sold }, property_id);
+    }
+    Ok(true)
+}
+
+// Query function to get the bids placed in a property's current auction, oldest first
+#[query]
+fn get_bids(property_id: String) -> Vec<Bid> {
+    let property_id = normalize_id(&property_id);
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        platform
+            .properties
+            .get(&property_id)
+            .and_then(|property| property.auction)
+            .map(|auction| auction.bids)
+            .unwrap_or_default()
+    })
+}
+
+// Function to bookmark a listing for the caller
+#[update]
+fn add_favorite(property_id: String) -> Result<(), PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let property_id = normalize_id(&property_id);
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        if !platform.properties.contains_key(&property_id) {
+            return Err(PlatformError::NotFound);
+        }
+        let favorites = platform.favorites.entry(env::caller()).or_default();
+        if favorites.len() >= MAX_FAVORITES && !favorites.contains(&property_id) {
+            return Err(PlatformError::LimitReached(format!(
+                "A principal can have at most {} favorites.",
+                MAX_FAVORITES
+            )));
+        }
+        favorites.insert(property_id);
+        Ok(())
+    })
+}
+
+// Function to drop a listing from the caller's favorites; removing one that isn't there is a no-op
+#[update]
+fn remove_favorite(property_id: String) -> Result<(), PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let property_id = normalize_id(&property_id);
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let caller = env::caller();
+        if let Some(favorites) = platform.favorites.get_mut(&caller) {
+            favorites.remove(&property_id);
+            if favorites.is_empty() {
+                platform.favorites.remove(&caller);
+            }
+        }
+    });
+    Ok(())
+}
+
+// Query function to get the caller's favorite listings, skipping any that are currently deleted
+#[query]
+fn get_favorites() -> Vec<Property> {
+    let now = env::time();
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        let caller = env::caller();
+        let Some(favorites) = platform.favorites.get(&caller) else {
+            return Vec::new();
+        };
+        // A listing made private after being bookmarked is hidden unless the caller was granted access
+        let mut properties: Vec<Property> = favorites
+            .iter()
+            .filter_map(|property_id| platform.properties.get(property_id))
+            .filter(|property| property.is_visible_to(caller))
+            .filter_map(|property| property.listed_view(now))
+            .collect();
+        properties.sort_by(|a, b| a.id.cmp(&b.id));
+        properties
+    })
+}
+
+// Function to start an empty collection owned by the caller
+#[update]
+fn create_collection(collection_id: String, name: String) -> Result<(), PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let collection_id = normalize_id(&collection_id);
+    if collection_id.is_empty() {
+        return Err(PlatformError::InvalidInput("The collection ID is empty.".to_string()));
+    }
+    if name.len() > MAX_COLLECTION_NAME_LEN {
+        return Err(PlatformError::InvalidInput("The collection name is too long.".to_string()));
+    }
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        if platform.collections.contains_key(&collection_id) {
+            return Err(PlatformError::DuplicateId { id: collection_id });
+        }
+        let collection = Collection {
+            id: collection_id.clone(),
+            name,
+            owner: env::caller(),
+            member_ids: Vec::new(),
+        };
+        platform.collections.insert(collection_id, collection);
+        Ok(())
+    })
+}
+
+// Function to add one of the caller's live properties to their collection; adding a member again is a no-op
+#[update]
+fn add_to_collection(collection_id: String, property_id: String) -> Result<(), PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let collection_id = normalize_id(&collection_id);
+    let property_id = normalize_id(&property_id);
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let caller = env::caller();
+        let property_owner = platform
+            .properties
+            .get(&property_id)
+            .map(|property| property.owner)
+            .ok_or(PlatformError::NotFound)?;
+        let collection = platform
+            .collections
+            .get_mut(&collection_id)
+            .ok_or(PlatformError::NotFound)?;
+        if collection.owner != caller {
+            return Err(PlatformError::Unauthorized);
+        }
+        if property_owner != collection.owner {
+            return Err(PlatformError::Unauthorized);
+        }
+        if collection.member_ids.contains(&property_id) {
+            return Ok(());
+        }
+        if collection.member_ids.len() >= MAX_COLLECTION_MEMBERS {
+            return Err(PlatformError::LimitReached(format!(
+                "A collection can hold at most {} properties.",
+                MAX_COLLECTION_MEMBERS
+            )));
+        }
+        collection.member_ids.push(property_id);
+        Ok(())
+    })
+}
+
+// Function to take a property out of the caller's collection; removing a non-member is a no-op
+#[update]
+fn remove_from_collection(collection_id: String, property_id: String) -> Result<(), PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let collection_id = normalize_id(&collection_id);
+    let property_id = normalize_id(&property_id);
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let collection = platform
+            .collections
+            .get_mut(&collection_id)
+            .ok_or(PlatformError::NotFound)?;
+        if collection.owner != env::caller() {
+            return Err(PlatformError::Unauthorized);
+        }
+        collection.member_ids.retain(|member_id| *member_id != property_id);
+        Ok(())
+    })
+}
+
+// Query function to get a collection with its members, leaving out any private ones the caller can't see
+#[query]
+fn get_collection(collection_id: String) -> Option<CollectionView> {
+    let collection_id = normalize_id(&collection_id);
+    let now = env::time();
+    let caller = env::caller();
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        let collection = platform.collections.get(&collection_id)?;
+        let members = collection
+            .member_ids
+            .iter()
+            .filter_map(|member_id| platform.properties.get(member_id))
+            .filter(|property| property.is_visible_to(caller))
+            .map(|property| property.view(now))
+            .collect();
+        Some(CollectionView {
+            id: collection.id.clone(),
+            name: collection.name.clone(),
+            owner: collection.owner,
+            members,
+        })
+    })
+}
+
+// Function to set the caller's own profile, replacing any earlier one
+#[update]
+fn set_profile(profile: Profile) -> Result<(), PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let display_name = profile.display_name.trim().to_string();
+    if display_name.is_empty() {
+        return Err(PlatformError::InvalidInput("The display name is empty.".to_string()));
+    }
+    if display_name.len() > MAX_DISPLAY_NAME_LEN {
+        return Err(PlatformError::InvalidInput("The display name is too long.".to_string()));
+    }
+    let avatar_hash = profile
+        .avatar_hash
+        .as_deref()
+        .map(parse_hash)
+        .transpose()?;
+
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        platform.borrow_mut().profiles.insert(
+            env::caller(),
+            Profile {
+                display_name,
+                avatar_hash,
+            },
+        );
+    });
+    Ok(())
+}
+
+// Query function to get the profile a principal has published, if any
+#[query]
+fn get_profile(principal: Principal) -> Option<Profile> {
+    DECENTRALIZED_PLATFORM.with(|platform| platform.borrow().profiles.get(&principal).cloned())
+}
+
+// Function to block a word or phrase in new descriptions and type labels, restricted to the admin.
+// Matching ignores case and punctuation and only hits whole words; existing listings aren't rechecked.
+#[update]
+fn add_blocked_keyword(keyword: String) {
+    require_admin();
+
+    let keyword = keyword_words(&keyword).join(" ");
+    if keyword.is_empty() {
+        env::trap("Blocked keywords must contain at least one letter or digit.");
+    }
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        platform.borrow_mut().blocked_keywords.insert(keyword);
+    });
+}
+
+// Function to unblock a keyword, restricted to the admin; unblocking a keyword that isn't blocked is a no-op
+#[update]
+fn remove_blocked_keyword(keyword: String) {
+    require_admin();
+
+    let keyword = keyword_words(&keyword).join(" ");
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        platform.borrow_mut().blocked_keywords.remove(&keyword);
+    });
+}
+
+// Function to approve a seller, restricted to the admin; the first approval turns the allowlist on
+#[update]
+fn add_seller(seller: Principal) {
+    require_admin();
+
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        platform
+            .borrow_mut()
+            .seller_allowlist
+            .get_or_insert_with(HashSet::new)
+            .insert(seller);
+    });
+}
+
+// Function to withdraw a seller's approval, restricted to the admin; their existing listings stay in place
+#[update]
+fn remove_seller(seller: Principal) {
+    require_admin();
+
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        if let Some(allowlist) = platform.borrow_mut().seller_allowlist.as_mut() {
+            allowlist.remove(&seller);
+        }
+    });
+}
+
+// Function to turn the seller allowlist on or off, restricted to the admin.
+// Turning it on keeps any sellers already approved; turning it off forgets them and opens uploads to everyone.
+#[update]
+fn set_seller_allowlist_enabled(enabled: bool) {
+    require_admin();
+
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        if enabled {
+            platform.seller_allowlist.get_or_insert_with(HashSet::new);
+        } else {
+            platform.seller_allowlist = None;
+        }
+    });
+}
+
+// Query function to check whether a principal may currently upload properties
+#[query]
+fn is_seller(principal: Principal) -> bool {
+    DECENTRALIZED_PLATFORM.with(|platform| platform.borrow().is_seller(principal))
+}
+
+// Function to register a principal allowed to vet listings, restricted to the admin
+#[update]
+fn add_verifier(verifier: Principal) {
+    require_admin();
+
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        platform.borrow_mut().verifiers.insert(verifier);
+    });
+}
+
+// Function to withdraw a verifier's role, restricted to the admin; badges they already granted stay in place
+#[update]
+fn remove_verifier(verifier: Principal) {
+    require_admin();
+
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        platform.borrow_mut().verifiers.remove(&verifier);
+    });
+}
+
+// Function to grant or withdraw a listing's verified badge, restricted to registered verifiers
+#[update]
+fn set_verified(property_id: String, verified: bool) -> Result<(), PlatformError> {
+    require_verifier();
+    ensure_not_paused()?;
+
+    let property_id = normalize_id(&property_id);
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let mut property = platform
+            .properties
+            .get_mut(&property_id)
+            .ok_or(PlatformError::NotFound)?;
+        if property.verified != verified {
+            property.verified = verified;
+            property.updated_at = env::time();
+            property.commit();
+            platform.record_event(AuditEventKind::VerificationChanged { verified }, &property_id);
+        }
+        Ok(())
+    })
+}
+
+// Function to pin or unpin a listing for promotion, restricted to the admin
+#[update]
+fn set_featured(property_id: String, featured: bool) -> Result<(), PlatformError> {
+    require_admin();
+
+    let property_id = normalize_id(&property_id);
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let mut property = platform
+            .properties
+            .get_mut(&property_id)
+            .ok_or(PlatformError::NotFound)?;
+        if property.featured != featured {
+            property.featured = featured;
+            property.updated_at = env::time();
+            property.commit();
+            platform.record_event(AuditEventKind::FeaturedChanged { featured }, &property_id);
+        }
+        Ok(())
+    })
+}
+
+// Query function to get every featured listing, ordered by property ID
+#[query]
+fn get_featured() -> Vec<Property> {
+    let now = env::time();
+    let caller = env::caller();
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        let mut featured: Vec<Property> = platform
+            .properties
+            .values()
+            .filter(|property| property.featured && property.is_visible_to(caller))
+            .map(|property| property.view(now))
+            .collect();
+        featured.sort_by(|a, b| a.id.cmp(&b.id));
+        featured
+    })
+}
+
+// Query function to list the registered verifiers
+#[query]
+fn get_verifiers() -> Vec<Principal> {
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut verifiers: Vec<Principal> = platform.borrow().verifiers.iter().copied().collect();
+        verifiers.sort();
+        verifiers
+    })
+}
+
+// Function to change the maximum accepted image size, restricted to the admin
+#[update]
+fn set_max_image_bytes(limit: u64) {
+    require_admin();
+
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        platform.borrow_mut().max_image_bytes = Some(limit);
+    });
+}
+
+// Function to set or clear the salt prepended to images before hashing, restricted to the admin.
+// Stored hashes would stop matching their images, so the salt can only change while nothing is stored.
+#[update]
+fn set_hash_salt(salt: Option<Vec<u8>>) {
+    require_admin();
+
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        if !platform.properties.is_empty() || !platform.trash.is_empty() {
+            env::trap("The hash salt can only be changed before any property is uploaded.");
+        }
+        platform.hash_salt = salt.filter(|salt| !salt.is_empty());
+    });
+}
+
+// Function to set or clear the image size limit for one property type, restricted to the admin.
+// Types without their own limit fall back to the platform-wide maximum.
+#[update]
+fn set_type_image_limit(property_type: PropertyType, limit: Option<u64>) {
+    require_admin();
+
+    let Ok(property_type) = normalize_property_type(property_type) else {
+        env::trap("Property type labels can't be empty.");
+    };
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        match limit {
+            Some(limit) => platform.type_image_limits.insert(type_label(&property_type), limit),
+            None => platform.type_image_limits.remove(&type_label(&property_type)),
+        };
+    });
+}
+
+// Function to set the attribute keys new uploads of a property type must include, restricted to the admin.
+// An empty list drops the requirement; existing listings aren't rechecked.
+#[update]
+fn set_required_attributes(property_type: PropertyType, keys: Vec<String>) {
+    require_admin();
+
+    let Ok(property_type) = normalize_property_type(property_type) else {
+        env::trap("Property type labels can't be empty.");
+    };
+    let mut required: Vec<String> = Vec::new();
+    for key in keys {
+        let key = key.trim().to_string();
+        if key.is_empty() {
+            env::trap("Attribute keys can't be empty.");
+        }
+        if !required.contains(&key) {
+            required.push(key);
+        }
+    }
+    if required.len() > MAX_ATTRIBUTES {
+        env::trap("A property type can't require more attributes than a property may have.");
+    }
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        if required.is_empty() {
+            platform.required_attributes.remove(&type_label(&property_type));
+        } else {
+            platform.required_attributes.insert(type_label(&property_type), required);
+        }
+    });
+}
+
+// Query function to get the required attribute keys of every property type that has some, sorted by type label
+#[query]
+fn get_required_attributes() -> Vec<(String, Vec<String>)> {
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        let mut schema: Vec<(String, Vec<String>)> = platform
+            .required_attributes
+            .iter()
+            .map(|(label, keys)| (label.clone(), keys.clone()))
+            .collect();
+        schema.sort_by(|a, b| a.0.cmp(&b.0));
+        schema
+    })
+}
+
+// Function to set the description template offered for new listings of a property type, restricted to the admin.
+// `None` or a blank template removes it; stored properties are never touched.
+#[update]
+fn set_template(property_type: PropertyType, template: Option<String>) {
+    require_admin();
+
+    let Ok(property_type) = normalize_property_type(property_type) else {
+        env::trap("Property type labels can't be empty.");
+    };
+    let template = template.filter(|template| !template.trim().is_empty());
+    if template.as_ref().is_some_and(|template| template.len() > MAX_DESCRIPTION_LEN) {
+        env::trap(&format!("Templates can be at most {} bytes.", MAX_DESCRIPTION_LEN));
+    }
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        match template {
+            Some(template) => platform.type_templates.insert(type_label(&property_type), template),
+            None => platform.type_templates.remove(&type_label(&property_type)),
+        };
+    });
+}
+
+// Query function to get the description template of a property type, if the admin set one
+#[query]
+fn get_template_for(property_type: PropertyType) -> Option<String> {
+    let property_type = normalize_property_type(property_type).ok()?;
+    DECENTRALIZED_PLATFORM.with(|platform| platform.borrow().type_templates.get(&type_label(&property_type)).cloned())
+}
+
+// Query function to get every description template, sorted by type label
+#[query]
+fn get_templates() -> Vec<(String, String)> {
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        let mut templates: Vec<(String, String)> = platform
+            .type_templates
+            .iter()
+            .map(|(label, template)| (label.clone(), template.clone()))
+            .collect();
+        templates.sort_by(|a, b| a.0.cmp(&b.0));
+        templates
+    })
+}
+
+// Query function to get the image size limit that applies to a property type
+#[query]
+fn get_image_limit(property_type: PropertyType) -> u64 {
+    let property_type = normalize_property_type(property_type.clone()).unwrap_or(property_type);
+    DECENTRALIZED_PLATFORM.with(|platform| platform.borrow().max_image_bytes_for(&property_type))
+}
+
+// Query function to get one page of a platform backup, restricted to the admin. Pages hold up to `limit`
+// entries, fewer if they would make the response too large; start at offset 0 and pass each page's
+// `next_offset` until it is `None`.
+#[query]
+fn export_state(offset: u64, limit: u64) -> SnapshotPage {
+    require_admin();
+
+    let limit = limit.clamp(1, MAX_PAGE_LIMIT);
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        let mut trash: Vec<&Property> = platform.trash.values().collect();
+        trash.sort_by(|a, b| a.id.cmp(&b.id));
+        let mut image_hashes: Vec<&String> = platform.images.keys().collect();
+        image_hashes.sort();
+        let mut owed_payouts: Vec<(Principal, u64)> =
+            platform.owed_payouts.iter().map(|(principal, owed)| (*principal, *owed)).collect();
+        owed_payouts.sort();
+        // The store already hands properties out in ID order
+        let mut entries = platform
+            .properties
+            .values()
+            .map(|property| SnapshotEntry::Property(Box::new(property)))
+            .chain(trash.into_iter().map(SnapshotEntry::Trashed))
+            .chain(image_hashes.into_iter().map(SnapshotEntry::Image))
+            .chain(platform.events.iter().map(SnapshotEntry::Event))
+            .chain(owed_payouts.into_iter().map(|(principal, owed)| SnapshotEntry::Payout(principal, owed)))
+            .skip(usize::try_from(offset).unwrap_or(usize::MAX))
+            .peekable();
+
+        let mut snapshot = PlatformSnapshot::default();
+        let mut taken = 0;
+        let mut size = 0usize;
+        while taken < limit {
+            let Some(entry) = entries.peek() else {
+                break;
+            };
+            let entry_size = match entry {
+                SnapshotEntry::Property(property) => response_size_estimate(property),
+                SnapshotEntry::Trashed(property) => response_size_estimate(property),
+                SnapshotEntry::Image(hash) => hash.len() + 8,
+                SnapshotEntry::Event(event) => candid::encode_one(event).map_or(usize::MAX, |encoded| encoded.len()),
+                SnapshotEntry::Payout(principal, _) => principal.as_slice().len() + 8,
+            };
+            size = size.saturating_add(entry_size);
+            if size > MAX_RESPONSE_BYTES && taken > 0 {
+                break;
+            }
+            match entries.next().expect("Peeked above") {
+                SnapshotEntry::Property(property) => snapshot.properties.push(*property),
+                SnapshotEntry::Trashed(property) => snapshot.trash.push(property.clone()),
+                // The length of the original bytes, so snapshots don't depend on how this canister stores them
+                SnapshotEntry::Image(hash) => {
+                    if let Some(len) = platform.original_image_len(hash) {
+                        snapshot.images.push((hash.clone(), len));
+                    }
+                }
+                SnapshotEntry::Event(event) => snapshot.events.push(event.clone()),
+                SnapshotEntry::Payout(principal, owed) => snapshot.owed_payouts.push((principal, owed)),
+            }
+            taken += 1;
+        }
+        let next_offset = entries.peek().is_some().then(|| offset.saturating_add(taken));
+        SnapshotPage { snapshot, next_offset }
+    })
+}
+
+// Function to stage one page of a snapshot for `import_state`, restricted to the admin. Pages are added in the
+// order they are sent; nothing is loaded until `import_state` runs.
+#[update]
+fn import_chunk(page: PlatformSnapshot) -> Result<(), PlatformError> {
+    require_admin();
+
+    ensure_no_import_proposal()?;
+    STAGED_IMPORT.with(|staged| staged.borrow_mut().snapshot.append(page));
+    Ok(())
+}
+
+// Query function to get up to `length` bytes of a stored image starting at `offset`, restricted to the admin,
+// so backups can copy images listed in `export_state` pages piece by piece. Returns the original bytes, and
+// fewer than asked for (none past the end) once the end of the image is reached.
+#[query]
+fn export_image_chunk(image_hash: String, offset: u64, length: u64) -> Result<Vec<u8>, PlatformError> {
+    require_admin();
+
+    let image_hash = parse_hash(&image_hash)?;
+    let image_data = DECENTRALIZED_PLATFORM
+        .with(|platform| platform.borrow().image_data(&image_hash))
+        .ok_or(PlatformError::NotFound)?;
+    let start = usize::try_from(offset).unwrap_or(usize::MAX).min(image_data.len());
+    let end = start
+        .saturating_add(length.min(MAX_IMAGE_CHUNK_BYTES) as usize)
+        .min(image_data.len());
+    Ok(image_data[start..end].to_vec())
+}
+
+// Function to stage the next piece of an image for `import_state`, restricted to the admin. Pieces of one image
+// must arrive in order, each starting at `offset` equal to the bytes staged for it so far.
+#[update]
+fn import_image_chunk(image_hash: String, offset: u64, bytes: Vec<u8>) -> Result<(), PlatformError> {
+    require_admin();
+
+    ensure_no_import_proposal()?;
+    let image_hash = parse_hash(&image_hash)?;
+    if bytes.len() as u64 > MAX_IMAGE_CHUNK_BYTES {
+        return Err(PlatformError::InvalidInput(format!(
+            "A chunk can be at most {} bytes.",
+            MAX_IMAGE_CHUNK_BYTES
+        )));
+    }
+    STAGED_IMPORT.with(|staged| {
+        let mut staged = staged.borrow_mut();
+        let image_data = staged.images.entry(image_hash).or_default();
+        if offset != image_data.len() as u64 {
+            return Err(PlatformError::InvalidInput(format!(
+                "The next chunk of this image starts at offset {}.",
+                image_data.len()
+            )));
+        }
+        image_data.extend_from_slice(&bytes);
+        Ok(())
+    })
+}
+
+// Function to drop every page and image staged with `import_chunk` and `import_image_chunk`, restricted to the admin
+#[update]
+fn discard_staged_import() -> Result<(), PlatformError> {
+    require_admin();
+
+    ensure_no_import_proposal()?;
+    STAGED_IMPORT.with(|staged| staged.take());
+    Ok(())
+}
+
+// Helper function to keep the staged snapshot fixed while admins are voting on loading it
+fn ensure_no_import_proposal() -> Result<(), PlatformError> {
+    let pending = DECENTRALIZED_PLATFORM.with(|platform| {
+        let now = env::time();
+        platform.borrow().proposals.values().any(|proposal| {
+            proposal.expires_at > now && matches!(proposal.action, AdminAction::ImportState { .. })
+        })
+    });
+    if pending {
+        return Err(PlatformError::Conflict("An import is waiting for approval.".to_string()));
+    }
+    Ok(())
+}
+
+// Function to load the snapshot staged with `import_chunk` and `import_image_chunk`, restricted to the admin.
+// Refuses to touch a platform that already has data unless `force` is set, in which case it is replaced.
+// The staged pages are used up either way, so a refused import has to be staged again.
+#[update]
+fn import_state(force: bool) -> Result<(), PlatformError> {
+    require_admin();
+    require_sole_approval();
+
+    apply_staged_import(force)
+}
+
+// Helper function that loads the staged snapshot, for `import_state` and approved proposals
+fn apply_staged_import(force: bool) -> Result<(), PlatformError> {
+    let StagedImport { snapshot, images } = STAGED_IMPORT.with(|staged| staged.take());
+    apply_snapshot(snapshot, images, force)
+}
+
+// Helper function that checks a snapshot and its image bytes and loads them, for `import_state` and approved
+// proposals
+fn apply_snapshot(
+    snapshot: PlatformSnapshot,
+    mut image_bytes: HashMap<String, Vec<u8>>,
+    force: bool,
+) -> Result<(), PlatformError> {
+    let PlatformSnapshot {
+        properties,
+        trash,
+        images: image_lens,
+        events,
+        owed_payouts,
+    } = snapshot;
+    let mut images: HashMap<String, (Vec<u8>, u32)> = HashMap::new();
+    for (hash, len) in image_lens {
+        let image_data = image_bytes
+            .remove(&hash)
+            .filter(|image_data| image_data.len() as u64 == len)
+            .ok_or_else(|| PlatformError::InvalidInput(format!("Image {} wasn't fully staged.", hash)))?;
+        images.insert(hash, (image_data, 0));
+    }
+
+    // Check the snapshot is self-consistent before replacing anything
+    let mut live = HashMap::new();
+    let mut trashed = HashMap::new();
+    let entries = properties
+        .into_iter()
+        .map(|property| (property, false))
+        .chain(trash.into_iter().map(|property| (property, true)));
+    for (property, is_trashed) in entries {
+        if live.contains_key(&property.id) || trashed.contains_key(&property.id) {
+            return Err(PlatformError::DuplicateId { id: property.id });
+        }
+        if let Some(hash) = property.stored_hashes().find(|hash| !images.contains_key(*hash)) {
+            return Err(PlatformError::InvalidInput(format!("The snapshot is missing image {}.", hash)));
+        }
+        let target = if is_trashed { &mut trashed } else { &mut live };
+        target.insert(property.id.clone(), property);
+    }
+    if events.windows(2).any(|pair| pair[0].id >= pair[1].id) {
+        return Err(PlatformError::InvalidInput("The snapshot's event IDs must increase.".to_string()));
+    }
+
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        // Payouts still owed count as data too, so a plain import can't drop what principals are waiting to claim
+        let is_empty = platform.properties.is_empty()
+            && platform.trash.is_empty()
+            && platform.images.is_empty()
+            && platform.owed_payouts.is_empty();
+        if !is_empty && !force {
+            return Err(PlatformError::Conflict(
+                "The platform already holds data; pass `force` to replace it.".to_string(),
+            ));
+        }
+
+        platform.properties.clear();
+        for (id, property) in live {
+            platform.properties.insert(id, property);
+        }
+        platform.trash = trashed;
+        platform.images = images;
+        platform.compressed_images.clear();
+        platform.recount_image_refs();
+        platform.backfill_image_sizes();
+        // Keep event IDs increasing past both the imported events and any handed out before
+        let after_imported = events.last().map_or(0, |event| event.id + 1);
+        platform.next_event_id = platform.next_event_id.max(after_imported);
+        platform.events = events;
+        platform.owed_payouts = owed_payouts.into_iter().collect();
+        // The transaction log and tombstones describe the properties being replaced, not the imported ones
+        platform.transactions.clear();
+        platform.deletions.clear();
+        // Snapshots don't carry version history, favorites, pending swaps, or collections, and what the platform
+        // held of them may point at properties that are gone or now belong to someone else
+        platform.versions.clear();
+        platform.version_times.clear();
+        platform.favorites.clear();
+        platform.swap_approvals.clear();
+        platform.collections.clear();
+        certify_all(&platform);
+        index_all(&platform);
+        platform.record_event(AuditEventKind::StateImported, "");
+        Ok(())
+    })
+}
+
+// Function to wipe all properties, images, events, and settings, restricted to the admin.
+// Meant for local development and tests; the admin role and the settings given at install (hash salt, image
+// size limit, and per-owner quota) are kept. Safe to call on an empty platform.
+#[update]
+fn reset_platform() {
+    require_admin();
+    require_sole_approval();
+
+    wipe_platform();
+}
+
+// Helper function that wipes the platform, for `reset_platform` and approved proposals
+fn wipe_platform() {
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        // Event IDs keep counting up so indexers resuming with `events_after` see the reset, and the admin
+        // council stays so a reset can't be used to get around the approval threshold. Payouts still owed
+        // stay claimable, since the canister holds that money, and the install-time settings stay as installed.
+        let next_event_id = platform.next_event_id;
+        let admin_council = std::mem::take(&mut platform.admin_council);
+        let approval_threshold = platform.approval_threshold;
+        let owed_payouts = std::mem::take(&mut platform.owed_payouts);
+        let hash_salt = platform.hash_salt.take();
+        let max_image_bytes = platform.max_image_bytes;
+        let max_properties_per_owner = platform.max_properties_per_owner;
+        // A fresh platform reopens the same stable map, so the properties have to be cleared out of it first
+        platform.properties.clear();
+        *platform = DecentralizedPlatform::default();
+        platform.next_event_id = next_event_id;
+        platform.admin_council = admin_council;
+        platform.approval_threshold = approval_threshold;
+        platform.owed_payouts = owed_payouts;
+        platform.hash_salt = hash_salt;
+        platform.max_image_bytes = max_image_bytes;
+        platform.max_properties_per_owner = max_properties_per_owner;
+        certify_all(&platform);
+        index_all(&platform);
+        platform.record_event(AuditEventKind::PlatformReset, "");
+    });
+    UPLOAD_SESSIONS.with(|sessions| sessions.borrow_mut().clear());
+    UPLOAD_HISTORY.with(|history| history.borrow_mut().clear());
+    IDEMPOTENT_UPLOADS.with(|uploads| uploads.borrow_mut().clear());
+    // The sweep interval is back to its default too
+    start_reservation_sweep();
+}
+
+// Function to choose the admins besides the primary one and how many of them must approve destructive actions,
+// restricted to the primary admin while a single approval suffices; afterwards it changes only by proposal
+#[update]
+fn set_admin_council(admins: Vec<Principal>, threshold: u32) {
+    require_admin();
+    require_sole_approval();
+
+    if let Some(problem) = admin_council_problem(&admins, threshold) {
+        env::trap(&problem);
+    }
+    apply_admin_council(admins, threshold);
+}
+
+// Helper function to explain why a council can't be installed, e.g. because the primary admin and the given
+// admins together can't meet the threshold
+fn admin_council_problem(admins: &[Principal], threshold: u32) -> Option<String> {
+    let primary = get_admin();
+    let members = admins.iter().filter(|admin| Some(**admin) != primary).collect::<HashSet<_>>().len() + 1;
+    if threshold == 0 || threshold as usize > members {
+        return Some(format!("The threshold must be between 1 and the {} admins.", members));
+    }
+    if admins.contains(&Principal::anonymous()) {
+        return Some("The anonymous principal can't be an admin.".to_string());
+    }
+    None
+}
+
+// Helper function to install a new admin council; pending proposals are dropped since their approvals may no
+// longer count
+fn apply_admin_council(admins: Vec<Principal>, threshold: u32) {
+    let primary = get_admin();
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        platform.admin_council = admins.into_iter().filter(|admin| Some(*admin) != primary).collect();
+        platform.approval_threshold = threshold;
+        platform.proposals.clear();
+    });
+}
+
+// Query function to list the admin council, sorted, along with the approval threshold
+#[query]
+fn get_admin_council() -> (Vec<Principal>, u32) {
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        let mut admins: Vec<Principal> = platform.admin_council.iter().copied().collect();
+        admins.sort();
+        (admins, platform.approval_threshold.max(1))
+    })
+}
+
+// Function to propose a destructive admin action, restricted to admins; returns the proposal ID.
+// The proposer's approval counts, so when a single approval suffices the action runs at once.
+#[update]
+fn propose_action(action: AdminAction) -> Result<u64, PlatformError> {
+    require_council_member();
+
+    match &action {
+        AdminAction::SetAdminCouncil { admins, threshold } => {
+            if let Some(problem) = admin_council_problem(admins, *threshold) {
+                return Err(PlatformError::InvalidInput(problem));
+            }
+        }
+        AdminAction::CleanUpAnonymousOwned { new_owner: Some(new_owner) } if *new_owner == Principal::anonymous() => {
+            return Err(PlatformError::InvalidInput(ANONYMOUS_REASSIGNMENT.to_string()));
+        }
+        _ => {}
+    }
+    let proposer = env::caller();
+    let now = env::time();
+    let id = DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        platform.proposals.retain(|_, proposal| proposal.expires_at > now);
+        let id = platform.next_proposal_id;
+        platform.next_proposal_id += 1;
+        platform.proposals.insert(
+            id,
+            Proposal {
+                id,
+                action,
+                proposer,
+                approvals: vec![proposer],
+                created_at: now,
+                expires_at: now.saturating_add(PROPOSAL_TTL_NANOS),
+            },
+        );
+        id
+    });
+    execute_if_approved(id)?;
+    Ok(id)
+}
+
+// Function to approve a pending admin action, restricted to admins. Returns whether this approval met the
+// threshold and ran the action; a failing action is not kept for another try.
+#[update]
+fn approve_action(proposal_id: u64) -> Result<bool, PlatformError> {
+    require_council_member();
+
+    let approver = env::caller();
+    let now = env::time();
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        platform.proposals.retain(|_, proposal| proposal.expires_at > now);
+        let proposal = platform.proposals.get_mut(&proposal_id).ok_or(PlatformError::NotFound)?;
+        if proposal.approvals.contains(&approver) {
+            return Err(PlatformError::Conflict("The caller already approved this proposal.".to_string()));
+        }
+        proposal.approvals.push(approver);
+        Ok(())
+    })?;
+    execute_if_approved(proposal_id)
+}
+
+// Helper function to run a proposal once enough distinct admins approved it; returns whether it ran
+fn execute_if_approved(proposal_id: u64) -> Result<bool, PlatformError> {
+    let approved = DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let threshold = platform.approval_threshold.max(1) as usize;
+        let ready = platform
+            .proposals
+            .get(&proposal_id)
+            .is_some_and(|proposal| proposal.approvals.len() >= threshold);
+        if ready {
+            platform.proposals.remove(&proposal_id)
+        } else {
+            None
+        }
+    });
+    let Some(proposal) = approved else {
+        return Ok(false);
+    };
+    match proposal.action {
+        AdminAction::ResetPlatform => wipe_platform(),
+        AdminAction::ImportState { force } => apply_staged_import(force)?,
+        AdminAction::SetAdminCouncil { admins, threshold } => apply_admin_council(admins, threshold),
+        AdminAction::RollbackTo { timestamp } => {
+            roll_back(timestamp)?;
+        }
+        AdminAction::DeletePropertiesByOwner { owner } => {
+            delete_matching(|property| property.owner == owner);
+        }
+        AdminAction::DeletePropertiesByType { property_type } => {
+            delete_matching(|property| property.property_type == property_type);
+        }
+        AdminAction::RemoveOrphaned => {
+            remove_orphaned_properties();
+        }
+        AdminAction::CleanUpAnonymousOwned { new_owner } => {
+            reassign_anonymous_owned(new_owner);
+        }
+        AdminAction::RehashAll { new_algo } => {
+            rehash_properties(new_algo);
+        }
+        AdminAction::PurgeEventsBefore { timestamp } => {
+            purge_events(timestamp);
+        }
+        AdminAction::PruneDeletions { before } => {
+            drop_deletions_before(before);
+        }
+        AdminAction::SetLedgerCanister { ledger } => use_ledger_canister(ledger),
+    }
+    Ok(true)
+}
+
+// Query function to list the admin actions still collecting approvals, oldest first, restricted to admins
+#[query]
+fn get_pending_proposals() -> Vec<PendingProposal> {
+    require_council_member();
+
+    let now = env::time();
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        platform
+            .proposals
+            .values()
+            .filter(|proposal| proposal.expires_at > now)
+            .map(|proposal| PendingProposal {
+                id: proposal.id,
+                action: proposal.action.name().to_string(),
+                proposer: proposal.proposer,
+                approvals: proposal.approvals.clone(),
+                threshold: platform.approval_threshold.max(1),
+                created_at: proposal.created_at,
+                expires_at: proposal.expires_at,
+            })
+            .collect()
+    })
+}
+
+// Function to have a canister method called with every new audit event, restricted to the admin
+#[update]
+fn subscribe(callback_canister: Principal, method: String) {
+    require_admin();
+
+    let subscriber = Subscriber {
+        canister: callback_canister,
+        method,
+    };
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        if platform.subscribers.contains(&subscriber) {
+            return;
+        }
+        if platform.subscribers.len() >= MAX_SUBSCRIBERS {
+            env::trap(&format!("At most {} subscribers can be registered.", MAX_SUBSCRIBERS));
+        }
+        platform.subscribers.push(subscriber);
+    });
+}
+
+// Function to stop notifying a subscriber, restricted to the admin; removing a missing subscriber is a no-op
+#[update]
+fn unsubscribe(callback_canister: Principal, method: String) {
+    require_admin();
+
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        platform
+            .borrow_mut()
+            .subscribers
+            .retain(|subscriber| subscriber.canister != callback_canister || subscriber.method != method);
+    });
+}
+
+// Query function to list the canisters notified of audit events, in the order they subscribed
+#[query]
+fn list_subscribers() -> Vec<Subscriber> {
+    DECENTRALIZED_PLATFORM.with(|platform| platform.borrow().subscribers.clone())
+}
+
+// Function to pause or resume all non-admin updates, restricted to the admin; queries keep working
+#[update]
+fn set_paused(paused: bool) {
+    require_admin();
+
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        platform.borrow_mut().paused = paused;
+    });
+}
+
+// Function to choose whether uploads of an already listed image are refused, restricted to the admin
+#[update]
+fn set_block_duplicate_images(block: bool) {
+    require_admin();
+
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        platform.borrow_mut().block_duplicate_images = block;
+    });
+}
+
+// Query function to check whether the platform is paused for maintenance
+#[query]
+fn is_paused() -> bool {
+    DECENTRALIZED_PLATFORM.with(|platform| platform.borrow().paused)
+}
+
+// Function to change how many uploads each principal may make per hour, restricted to the admin
+#[update]
+fn set_max_uploads_per_hour(limit: u32) {
+    require_admin();
+
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        platform.borrow_mut().max_uploads_per_hour = Some(limit);
+    });
+}
+
+// Function to set or clear the most properties one principal may hold, restricted to the admin.
+// Owners already at or above the limit keep what they have but can't upload, buy outright, or be sent more.
+#[update]
+fn set_max_properties_per_owner(limit: Option<u64>) {
+    require_admin();
+
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        platform.borrow_mut().max_properties_per_owner = limit;
+    });
+}
+
+// Function to set or clear the most live properties the platform may hold, restricted to the admin.
+// A platform already at or above the limit keeps its properties but takes no new uploads.
+#[update]
+fn set_max_total_properties(limit: Option<u64>) {
+    require_admin();
+
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        platform.borrow_mut().max_total_properties = limit;
+    });
+}
+
+// Function to set or clear how long a property must stay with its owner before `transfer_property`,
+// `transfer_all`, or `swap_properties` can move it again, restricted to the admin. Paid sales and auctions still
+// go through during the cooldown.
+#[update]
+fn set_transfer_cooldown(secs: Option<u64>) {
+    require_admin();
+
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        platform.borrow_mut().transfer_cooldown_secs = secs;
+    });
+}
+
+// Function to change how often lapsed reservations are swept, in seconds, restricted to the admin
+#[update]
+fn set_reservation_sweep_interval(secs: u64) {
+    require_admin();
+    if secs == 0 {
+        env::trap("The sweep interval must be at least one second.");
+    }
+
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        platform.borrow_mut().reservation_sweep_secs = Some(secs);
+    });
+    start_reservation_sweep();
+}
+
+// Function to store the `Expired` status of every lapsed listing, restricted to the admin; returns how many changed
+#[update]
+fn sweep_expired() -> u64 {
+    require_admin();
+
+    let now = env::time();
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let expired: Vec<Property> = platform
+            .properties
+            .values()
+            .filter(|property| property.has_expired(now))
+            .map(|mut property| {
+                property.status = ListingStatus::Expired;
+                property.reservation = None;
+                property.updated_at = now;
+                property
+            })
+            .collect();
+        for property in &expired {
+            let property_id = &property.id;
+            platform.properties.insert(property_id.clone(), property.clone());
+            let status = ListingStatus::Expired;
+            platform.record_event(AuditEventKind::StatusChanged { status }, property_id);
+        }
+        expired.len() as u64
+    })
+}
+
+// Function to drop audit events older than the given timestamp, restricted to the admin
+#[update]
+fn purge_events_before(timestamp: u64) -> u64 {
+    require_admin();
+    require_sole_approval();
+
+    purge_events(timestamp)
+}
+
+// Helper function that drops audit events older than `timestamp`, for `purge_events_before` and approved proposals
+fn purge_events(timestamp: u64) -> u64 {
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let before = platform.events.len();
+        platform.events.retain(|event| event.timestamp >= timestamp);
+        (before - platform.events.len()) as u64
+    })
+}
+
+// Helper function to recognize an image's format from its magic bytes
+fn detect_format(data: &[u8]) -> Option<ImageFormat> {
+    if data.starts_with(&[0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a]) {
+        Some(ImageFormat::Png)
+    } else if data.starts_with(&[0xff, 0xd8, 0xff]) {
+        Some(ImageFormat::Jpeg)
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        Some(ImageFormat::Gif)
+    } else if data.len() >= 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WEBP" {
+        Some(ImageFormat::WebP)
+    } else {
+        None
+    }
+}
+
+// Helper function to hash image data with the given algorithm, prefixed by the deployment's salt (if any)
+fn hash_image(image_data: &[u8], hash_algo: HashAlgo, salt: &[u8]) -> String {
+    let salted;
+    let input = if salt.is_empty() {
+        image_data
+    } else {
+        salted = [salt, image_data].concat();
+        &salted
+    };
+    // Convert the hash to a hexadecimal string
+    match hash_algo {
+        HashAlgo::Sha256 => hex::encode(Sha256::digest(input)),
+        HashAlgo::Sha512 => hex::encode(Sha512::digest(input)),
+        HashAlgo::Blake3 => hex::encode(blake3::hash(input).as_bytes()),
+    }
+}
+
+// Query function to get all property IDs and their associated primary image hashes
+#[query]
+fn get_properties() -> Vec<(String, String, String)> {
+    let now = env::time();
+    let caller = env::caller();
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        platform
+            .properties
+            .iter()
+            // Expired listings drop off this overview
+            .filter(|(_, property)| property.status != ListingStatus::Expired && !property.has_expired(now))
+            .filter(|(_, property)| property.is_visible_to(caller))
+            .map(|(id, property)| {
+                let primary_hash = property.image_hashes.first().cloned().unwrap_or_default();
+                (id, format!("{:?}", property.property_type), primary_hash)
+            })
+            .collect()
+    })
+}
+
+// Query function to get a page of properties, ordered by property ID so pages are stable
+#[query]
+fn get_properties_paged(offset: u64, limit: u64) -> PropertyPage {
+    let caller = env::caller();
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        let mut properties: Vec<Property> = platform
+            .properties
+            .values()
+            .filter(|property| property.is_visible_to(caller))
+            .collect();
+        properties.sort_by(|a, b| a.id.cmp(&b.id));
+        paginate(properties, offset, limit)
+    })
+}
+
+// Query function to get a page of properties in the requested order, e.g. newest or cheapest first.
+// With `featured_first`, featured listings come before all others, each group in the requested order.
+// Ties always fall back to ascending property ID so pages stay stable between calls.
+#[query]
+fn get_properties_sorted(
+    sort: SortKey,
+    descending: bool,
+    offset: u64,
+    limit: u64,
+    featured_first: Option<bool>,
+) -> PropertyPage {
+    let featured_first = featured_first.unwrap_or(false);
+    let caller = env::caller();
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        let mut properties: Vec<Property> = platform
+            .properties
+            .values()
+            .filter(|property| property.is_visible_to(caller))
+            .collect();
+        properties.sort_by(|a, b| {
+            let ordering = match sort {
+                SortKey::CreatedAt => a.created_at.cmp(&b.created_at),
+                SortKey::Price => a.price_e8s.cmp(&b.price_e8s),
+                SortKey::Id => a.id.cmp(&b.id),
+            };
+            let ordering = if descending { ordering.reverse() } else { ordering };
+            let featured = if featured_first { b.featured.cmp(&a.featured) } else { Ordering::Equal };
+            featured.then(ordering).then_with(|| a.id.cmp(&b.id))
+        });
+        paginate(properties, offset, limit)
+    })
+}
+
+// Query function to get a page of available properties priced between `min_e8s` and `max_e8s` (inclusive),
+// cheapest first with ties broken by ID. An inverted range yields an empty page.
+#[query]
+fn get_properties_in_price_range(min_e8s: u64, max_e8s: u64, offset: u64, limit: u64) -> PropertyPage {
+    let now = env::time();
+    let caller = env::caller();
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        let mut properties: Vec<Property> = platform
+            .properties
+            .values()
+            .filter(|property| (min_e8s..=max_e8s).contains(&property.price_e8s))
+            .filter(|property| property.status == ListingStatus::Available && !property.has_expired(now))
+            .filter(|property| property.is_visible_to(caller))
+            .collect();
+        properties.sort_by(|a, b| a.price_e8s.cmp(&b.price_e8s).then_with(|| a.id.cmp(&b.id)));
+        paginate(properties, offset, limit)
+    })
+}
+
+// Query function to get the properties whose IDs sort after `cursor`, the last ID of the previous page.
+// Unlike offsets, the cursor stays valid when properties are added or removed between calls.
+#[query]
+fn get_properties_after(cursor: Option<String>, limit: u64) -> CursorPage {
+    let now = env::time();
+    let limit = limit.min(MAX_PAGE_LIMIT) as usize;
+    let caller = env::caller();
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        let mut properties: Vec<Property> = platform
+            .properties
+            .values()
+            .filter(|property| cursor.as_ref().is_none_or(|cursor| property.id > *cursor))
+            .filter(|property| property.is_visible_to(caller))
+            .collect();
+        properties.sort_by(|a, b| a.id.cmp(&b.id));
+        let has_more = properties.len() > limit;
+        let (entries, truncated) = fit_response(properties.into_iter().take(limit).map(|property| property.view(now)));
+        let next_cursor = if has_more || truncated { entries.last().map(|property| property.id.clone()) } else { None };
+        CursorPage { entries, next_cursor }
+    })
+}
+
+// Helper function to cut one page out of an already ordered list of properties
+fn paginate(properties: Vec<Property>, offset: u64, limit: u64) -> PropertyPage {
+    let now = env::time();
+    let total = properties.len() as u64;
+    let (entries, truncated) = fit_response(
+        properties
+            .into_iter()
+            .skip(usize::try_from(offset).unwrap_or(usize::MAX))
+            .take(limit.min(MAX_PAGE_LIMIT) as usize)
+            .map(|property| property.view(now)),
+    );
+    PropertyPage {
+        entries,
+        total,
+        truncated,
+    }
+}
+
+// Helper function to estimate how many bytes a property adds to a Candid-encoded response
+fn response_size_estimate(property: &Property) -> usize {
+    candid::encode_one(property).map_or(usize::MAX, |encoded| encoded.len())
+}
+
+// Helper function to take entries while their estimated size stays within MAX_RESPONSE_BYTES, returning them
+// and whether any were left out. The first entry is always kept so callers paging through can make progress.
+fn fit_response(properties: impl Iterator<Item = Property>) -> (Vec<Property>, bool) {
+    let mut entries = Vec::new();
+    let mut size = 0usize;
+    for property in properties {
+        size = size.saturating_add(response_size_estimate(&property));
+        if size > MAX_RESPONSE_BYTES && !entries.is_empty() {
+            return (entries, true);
+        }
+        entries.push(property);
+    }
+    (entries, false)
+}
+
+// Query function to get a specific property's details by ID, with a certificate and witness
+// proving the record matches the canister's certified data
+#[query]
+fn get_property_by_id(property_id: String) -> Option<CertifiedProperty> {
+    let property_id = normalize_id(&property_id);
+    let stored = DECENTRALIZED_PLATFORM.with(|platform| platform.borrow().properties.get(&property_id))?;
+    // Private listings look missing to anyone they weren't shared with
+    if !stored.is_visible_to(env::caller()) {
+        return None;
+    }
+    let property = stored.view(env::time());
+    // Only the stored record is certified, so a lazily expired view can't come with a certificate
+    let certificate = if property.status == stored.status {
+        env::data_certificate()
+    } else {
+        None
+    };
+
+    let witness = CERTIFIED_DIGESTS.with(|digests| {
+        let digests = digests.borrow();
+        let tree = ic_certified_map::labeled(CERTIFIED_PROPERTIES_LABEL, digests.witness(property_id.as_bytes()));
+        // Prefixed with the CBOR self-describe tag, as agent libraries expect
+        let mut witness = vec![0xd9, 0xd9, 0xf7];
+        ciborium::into_writer(&tree, &mut witness).expect("Failed to encode witness");
+        witness
+    });
+
+    Some(CertifiedProperty {
+        property,
+        certificate,
+        witness,
+    })
+}
+
+// Function to count one view of a listing, returning the new total. Clients call it next to the
+// `get_property_by_id` query: an update goes through consensus, so it is slower and costs cycles, but it is the
+// only way to persist the count. Hidden listings are reported as `NotFound`, like missing ones.
+#[update]
+fn record_view(property_id: String) -> Result<u64, PlatformError> {
+    ensure_not_paused()?;
+
+    let property_id = normalize_id(&property_id);
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let mut property = platform
+            .properties
+            .get_mut(&property_id)
+            .ok_or(PlatformError::NotFound)?;
+        if !property.is_visible_to(env::caller()) {
+            return Err(PlatformError::NotFound);
+        }
+        property.views = property.views.saturating_add(1);
+        let views = property.views;
+        property.commit();
+        // Views aren't audited, but the count is part of the certified record
+        certify_property(&platform, &property_id);
+        Ok(views)
+    })
+}
+
+// Query function to get how many views a listing has recorded
+#[query]
+fn get_view_count(property_id: String) -> Option<u64> {
+    let property_id = normalize_id(&property_id);
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        platform
+            .borrow()
+            .properties
+            .get(&property_id)
+            .filter(|property| property.is_visible_to(env::caller()))
+            .map(|property| property.views)
+    })
+}
+
+// Query function to get the most recently uploaded properties, newest first, for a "just listed" feed
+#[query]
+fn get_recent(limit: u64) -> Vec<Property> {
+    let now = env::time();
+    let caller = env::caller();
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        let mut properties: Vec<Property> = platform
+            .properties
+            .values()
+            .filter(|property| property.is_visible_to(caller))
+            .collect();
+        properties.sort_by(|a, b| b.created_at.cmp(&a.created_at).then_with(|| a.id.cmp(&b.id)));
+        properties
+            .into_iter()
+            .take(limit.min(MAX_PAGE_LIMIT) as usize)
+            .map(|property| property.view(now))
+            .collect()
+    })
+}
+
+// Query function to look up several properties at once; results line up with `ids`, with `None` for
+// IDs that don't exist or aren't visible to the caller
+#[query]
+fn get_properties_by_ids(ids: Vec<String>) -> Vec<Option<Property>> {
+    if ids.len() > MAX_LOOKUP_IDS {
+        env::trap(&format!("At most {} properties can be looked up at once.", MAX_LOOKUP_IDS));
+    }
+
+    let now = env::time();
+    let caller = env::caller();
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        ids.iter()
+            .map(|id| {
+                platform
+                    .properties
+                    .get(&normalize_id(id))
+                    .filter(|property| property.is_visible_to(caller))
+                    .map(|property| property.view(now))
+            })
+            .collect()
+    })
+}
+
+// Query function to get a page of audit events, oldest first
+#[query]
+fn get_events(offset: u64, limit: u64) -> Vec<AuditEvent> {
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        platform
+            .borrow()
+            .events
+            .iter()
+            .skip(usize::try_from(offset).unwrap_or(usize::MAX))
+            .take(limit.min(MAX_PAGE_LIMIT) as usize)
+            .cloned()
+            .collect()
+    })
+}
+
+// Query function to get up to `limit` audit events with an ID above `event_id`, oldest first.
+// Indexers pass the last ID they saw to resume, which keeps working when old events are purged.
+#[query]
+fn events_after(event_id: u64, limit: u64) -> Vec<AuditEvent> {
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        let start = platform.events.partition_point(|event| event.id <= event_id);
+        platform.events[start..]
+            .iter()
+            .take(limit.min(MAX_PAGE_LIMIT) as usize)
+            .cloned()
+            .collect()
+    })
+}
+
+// Query function to get up to `length` transactions starting at index `start`, oldest first
+#[query]
+fn get_transactions(start: u64, length: u64) -> Vec<Transaction> {
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        platform
+            .borrow()
+            .transactions
+            .iter()
+            .skip(usize::try_from(start).unwrap_or(usize::MAX))
+            .take(length.min(MAX_PAGE_LIMIT) as usize)
+            .cloned()
+            .collect()
+    })
+}
+
+// Function to buy a listing: the full price is pulled from the caller into this canister's account through
+// the ledger (ICRC-2, so the buyer must first approve this canister), then ownership passes to the caller and
+// the seller and creator are paid out of that escrow. If the sale can't complete, the buyer is refunded.
+#[update]
+async fn buy_property(property_id: String) -> Result<(), PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let property_id = normalize_id(&property_id);
+    let buyer = env::caller();
+    let now = env::time();
+    // Also stops a buyer from paying twice by calling again before the first payment settles
+    ensure_no_sale_in_flight(&property_id)?;
+
+    // Hold the listing for the buyer while the payment is in flight so nobody else can claim it
+    let (seller, creator, price_e8s, royalty_e8s, seller_e8s, ledger, placed_hold) = with_state_mut(|platform| {
+        let ledger = platform.ledger_canister();
+        let quota = quota_reached(platform, buyer);
+        let mut property = platform
+            .properties
+            .get_mut(&property_id)
+            .ok_or(PlatformError::NotFound)?;
+        if property.owner == buyer {
+            return Err(PlatformError::Unauthorized);
+        }
+        // Checked before paying, so a buyer at their limit is never charged
+        if let Some(limit) = quota {
+            return Err(PlatformError::QuotaExceeded { limit });
+        }
+        // The creator's cut of a resale; a creator selling their own property is paid in full as the seller
+        let royalty_bps = if property.creator == property.owner { 0 } else { property.royalty_bps };
+        let (royalty_e8s, seller_e8s) = royalty_split(property.price_e8s, royalty_bps)?;
+        let released = property.expire_reservation(now);
+
+        let held_by_buyer = property
+            .reservation
+            .as_ref()
+            .is_some_and(|reservation| reservation.reserved_by == buyer);
+        let expires_at = now.saturating_add(RESERVATION_TTL_NANOS);
+        if !held_by_buyer {
+            if property.status != ListingStatus::Available || property.has_expired(now) || property.auction.is_some()
+            {
+                return Err(PlatformError::NotAvailable);
+            }
+            property.status = ListingStatus::Pending;
+            property.reservation = Some(Reservation {
+                reserved_by: buyer,
+                expires_at,
+            });
+            property.updated_at = now;
+        }
+        let (seller, creator, price_e8s) = (property.owner, property.creator, property.price_e8s);
+        property.commit();
+        // Logged like any other hold, so the certified record matches what readers see during the payment
+        if released {
+            platform.record_event(AuditEventKind::ReservationExpired, &property_id);
+        }
+        if !held_by_buyer {
+            platform.record_event(AuditEventKind::Reserved { expires_at }, &property_id);
+        }
+        Ok((seller, creator, price_e8s, royalty_e8s, seller_e8s, ledger, !held_by_buyer))
+    })?;
+    SALES_IN_FLIGHT.with(|sales| sales.borrow_mut().insert(property_id.clone(), now));
+
+    // One transfer for the whole price, so the buyer is either charged in full or not at all
+    let payment = pull_payment(ledger, buyer, env::id(), price_e8s).await;
+    SALES_IN_FLIGHT.with(|sales| sales.borrow_mut().remove(&property_id));
+
+    let sold = with_state_mut(|platform| {
+        let failure = payment.err();
+
+        let Some(mut property) = platform.properties.get_mut(&property_id) else {
+            return failure.map_or(Ok(false), Err);
+        };
+        let still_held = property.owner == seller
+            && property
+                .reservation
+                .as_ref()
+                .is_some_and(|reservation| reservation.reserved_by == buyer);
+
+        // Leave the listing unsold, releasing the hold only if this call placed it
+        if let Some(error) = failure {
+            if placed_hold && still_held {
+                property.reservation = None;
+                property.status = ListingStatus::Available;
+                property.updated_at = env::time();
+                property.commit();
+                platform.record_event(AuditEventKind::ReservationCancelled, &property_id);
+            }
+            return Err(error);
+        }
+        if !still_held {
+            return Ok(false);
+        }
+
+        let now = env::time();
+        change_owner(&mut property, buyer, None, now);
+        property.status = ListingStatus::Sold;
+        property.commit();
+        platform.record_event(AuditEventKind::Transferred { to: buyer }, &property_id);
+        platform.record_event(AuditEventKind::StatusChanged { status: ListingStatus::Sold }, &property_id);
+        Ok(true)
+    })?;
+
+    if !sold {
+        pay_from_escrow(ledger, buyer, price_e8s).await;
+        return Err(PlatformError::SaleInterrupted);
+    }
+    pay_from_escrow(ledger, seller, seller_e8s).await;
+    if royalty_e8s > 0 {
+        pay_from_escrow(ledger, creator, royalty_e8s).await;
+    }
+    Ok(())
+}
+
+// Function to collect the caller's payouts that couldn't be sent when the sale or refund happened,
+// returning the e8s sent (before the ledger fee)
+#[update]
+async fn claim_payouts() -> Result<u64, PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let caller = env::caller();
+    let (ledger, amount_e8s) = with_state_mut(|platform| {
+        let amount_e8s = platform.owed_payouts.remove(&caller).ok_or(PlatformError::NotFound)?;
+        Ok((platform.ledger_canister(), amount_e8s))
+    })?;
+    if let Err(error) = send_from_escrow(ledger, caller, amount_e8s).await {
+        credit_payout(caller, amount_e8s);
+        return Err(error);
+    }
+    Ok(amount_e8s)
+}
+
+// Query function to get the e8s waiting in escrow for a principal to claim with `claim_payouts`
+#[query]
+fn get_owed_payout(principal: Principal) -> u64 {
+    DECENTRALIZED_PLATFORM.with(|platform| platform.borrow().owed_payouts.get(&principal).copied().unwrap_or(0))
+}
+
+// Helper function to pay `amount_e8s` out of escrow, holding it for `to` to claim later if the transfer fails
+async fn pay_from_escrow(ledger: Principal, to: Principal, amount_e8s: u64) {
+    if send_from_escrow(ledger, to, amount_e8s).await.is_err() {
+        credit_payout(to, amount_e8s);
+    }
+}
+
+// Helper function to add to what a principal can claim with `claim_payouts`
+fn credit_payout(to: Principal, amount_e8s: u64) {
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let owed = platform.owed_payouts.entry(to).or_insert(0);
+        *owed = owed.saturating_add(amount_e8s);
+    });
+}
+
+// Helper function to send `amount_e8s` from this canister's account to `to` with `icrc1_transfer`. The ledger
+// fee comes out of the amount; an amount that doesn't cover the fee is refused so it stays owed.
+async fn send_from_escrow(ledger: Principal, to: Principal, amount_e8s: u64) -> Result<(), PlatformError> {
+    let call_failed = |(code, message): (ic_cdk::api::call::RejectionCode, String)| PlatformError::CallFailed {
+        message: format!("{:?}: {}", code, message),
+    };
+    let (fee,): (Nat,) = ic_cdk::call(ledger, "icrc1_fee", ()).await.map_err(call_failed)?;
+    let fee_e8s = u64::try_from(&fee.0).map_err(|_| PlatformError::Overflow)?;
+    if amount_e8s <= fee_e8s {
+        return Err(PlatformError::InvalidInput("The amount doesn't cover the ledger fee.".to_string()));
+    }
+    let args = TransferArgs {
+        from_subaccount: None,
+        to: Account { owner: to, subaccount: None },
+        amount: Nat::from(amount_e8s - fee_e8s),
+        fee: Some(fee),
+        memo: None,
+        created_at_time: None,
+    };
+    // `icrc1_transfer`'s error variants are a subset of `icrc2_transfer_from`'s, so they decode into the same type
+    let transfer: (Result<Nat, TransferFromError>,) =
+        ic_cdk::call(ledger, "icrc1_transfer", (args,)).await.map_err(call_failed)?;
+    match transfer {
+        (Ok(_block_index),) => Ok(()),
+        (Err(error),) => Err(PlatformError::LedgerRejected { error }),
+    }
+}
+
+// Helper function to tell whether a buyer is paying for the listing right now
+fn sale_in_flight(property_id: &str, now: u64) -> bool {
+    SALES_IN_FLIGHT.with(|sales| {
+        sales
+            .borrow()
+            .get(property_id)
+            .is_some_and(|started_at| now < started_at.saturating_add(RESERVATION_TTL_NANOS))
+    })
+}
+
+// Helper function to refuse changes that would pull a listing out from under a buyer who is paying for it
+fn ensure_no_sale_in_flight(property_id: &str) -> Result<(), PlatformError> {
+    if sale_in_flight(property_id, env::time()) {
+        return Err(PlatformError::Conflict("A buyer is paying for this property right now.".to_string()));
+    }
+    Ok(())
+}
+
+// Helper function to split a sale price into the creator's royalty and the seller's remainder
+fn royalty_split(price_e8s: u64, royalty_bps: u16) -> Result<(u64, u64), PlatformError> {
+    let royalty_e8s = price_e8s
+        .checked_mul(u64::from(royalty_bps))
+        .map(|scaled| scaled / TOTAL_SHARES)
+        // Only enormous prices overflow the product; for those, dividing first rounds the royalty down slightly
+        .or_else(|| (price_e8s / TOTAL_SHARES).checked_mul(u64::from(royalty_bps)))
+        .ok_or(PlatformError::Overflow)?;
+    let seller_e8s = price_e8s.checked_sub(royalty_e8s).ok_or(PlatformError::Overflow)?;
+    Ok((royalty_e8s, seller_e8s))
+}
+
+// Helper function to move `amount_e8s` from `from` to `to` through the ledger, using this canister's
+// ICRC-2 allowance from `from`
+async fn pull_payment(ledger: Principal, from: Principal, to: Principal, amount_e8s: u64) -> Result<(), PlatformError> {
+    let args = TransferFromArgs {
+        spender_subaccount: None,
+        from: Account { owner: from, subaccount: None },
+        to: Account { owner: to, subaccount: None },
+        amount: Nat::from(amount_e8s),
+        fee: None,
+        memo: None,
+        created_at_time: None,
+    };
+    let payment: Result<(Result<Nat, TransferFromError>,), _> =
+        ic_cdk::call(ledger, "icrc2_transfer_from", (args,)).await;
+    match payment {
+        Ok((Ok(_block_index),)) => Ok(()),
+        Ok((Err(error),)) => Err(PlatformError::LedgerRejected { error }),
+        Err((code, message)) => Err(PlatformError::CallFailed {
+            message: format!("{:?}: {}", code, message),
+        }),
+    }
+}
+
+// Function to choose which ledger canister payments go through, restricted to the admin. Every later sale and
+// payout goes to whichever ledger is named here, so it needs the same approvals as the destructive actions.
+#[update]
+fn set_ledger_canister(ledger: Principal) {
+    require_admin();
+    require_sole_approval();
+
+    use_ledger_canister(ledger);
+}
+
+// Helper function that switches ledgers, for `set_ledger_canister` and approved proposals
+fn use_ledger_canister(ledger: Principal) {
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        platform.borrow_mut().ledger_canister = Some(ledger);
+    });
+}
+
+// Function to restrict uploads to the given property types, restricted to the admin.
+// Types are normalized as on upload; an empty list lifts the restriction again.
+#[update]
+fn set_allowed_types(types: Vec<PropertyType>) {
+    require_admin();
+
+    let mut allowed = HashSet::new();
+    for property_type in types {
+        match normalize_property_type(property_type) {
+            Ok(property_type) => allowed.insert(type_label(&property_type)),
+            Err(_) => env::trap("Property type labels can't be empty."),
+        };
+    }
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        platform.borrow_mut().allowed_types = if allowed.is_empty() { None } else { Some(allowed) };
+    });
+}
+
+// Query function to get the ledger canister payments go through
+#[query]
+fn get_ledger_canister() -> Principal {
+    DECENTRALIZED_PLATFORM.with(|platform| platform.borrow().ledger_canister())
+}
+
+// Query function to get the active hold on a listing; lapsed reservations read as none
+#[query]
+fn get_reservation(property_id: String) -> Option<Reservation> {
+    let property_id = normalize_id(&property_id);
+    let now = env::time();
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        platform
+            .borrow()
+            .properties
+            .get(&property_id)?
+            .reservation
+            .clone()
+            .filter(|reservation| reservation.expires_at > now)
+    })
+}
+
+// Query function to get the chain of custody of a property, oldest owner first
+#[query]
+fn get_provenance(property_id: String) -> Vec<OwnershipRecord> {
+    let property_id = normalize_id(&property_id);
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        platform
+            .borrow()
+            .properties
+            .get(&property_id)
+            .map(|property| property.owner_history.clone())
+            .unwrap_or_default()
+    })
+}
+
+// Query function to get a property's transfer receipts, oldest first
+#[query]
+fn get_transfers(property_id: String) -> Vec<TransferReceipt> {
+    let property_id = normalize_id(&property_id);
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        platform
+            .borrow()
+            .properties
+            .get(&property_id)
+            .map(|property| property.transfers.clone())
+            .unwrap_or_default()
+    })
+}
+
+// Query function to get the recent versions of a property, oldest first, ending with its current state
+#[query]
+fn get_property_versions(property_id: String) -> Vec<Property> {
+    let property_id = normalize_id(&property_id);
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        let visible = platform
+            .properties
+            .get(&property_id)
+            .is_some_and(|property| property.is_visible_to(env::caller()));
+        if !visible {
+            return Vec::new();
+        }
+        platform.versions.get(&property_id).cloned().unwrap_or_default()
+    })
+}
+
+// Query function to list the fields that differ between versions `a` and `b` of a property, as indexed in
+// `get_property_versions`; unknown indices give an empty list
+#[query]
+fn diff_versions(property_id: String, a: u32, b: u32) -> Vec<FieldChange> {
+    let versions = get_property_versions(property_id);
+    match (versions.get(a as usize), versions.get(b as usize)) {
+        (Some(old), Some(new)) => field_changes(old, new),
+        _ => Vec::new(),
+    }
+}
+
+// Helper function to compare the user-facing fields of two versions of a property
+fn field_changes(old: &Property, new: &Property) -> Vec<FieldChange> {
+    let fields = [
+        ("property_type", format!("{:?}", old.property_type), format!("{:?}", new.property_type)),
+        ("image_hashes", old.image_hashes.join(","), new.image_hashes.join(",")),
+        ("description", old.description.clone(), new.description.clone()),
+        ("owner", old.owner.to_text(), new.owner.to_text()),
+        ("price_e8s", old.price_e8s.to_string(), new.price_e8s.to_string()),
+        ("status", format!("{:?}", old.status), format!("{:?}", new.status)),
+        ("attributes", format!("{:?}", old.attributes), format!("{:?}", new.attributes)),
+        ("location", format!("{:?}", old.location), format!("{:?}", new.location)),
+        ("tags", old.tags.join(","), new.tags.join(",")),
+        ("expires_at", format!("{:?}", old.expires_at), format!("{:?}", new.expires_at)),
+        ("verified", old.verified.to_string(), new.verified.to_string()),
+        ("featured", old.featured.to_string(), new.featured.to_string()),
+        ("visibility", format!("{:?}", old.visibility), format!("{:?}", new.visibility)),
+        ("thumbnail_hash", format!("{:?}", old.thumbnail_hash), format!("{:?}", new.thumbnail_hash)),
+    ];
+    fields
+        .into_iter()
+        .filter(|(_, old, new)| old != new)
+        .map(|(field, old, new)| FieldChange {
+            field: field.to_string(),
+            old,
+            new,
+        })
+        .collect()
+}
+
+// Counts of what `rollback_to` did
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+struct RollbackReport {
+    changed: u64,  // Live properties put back to an earlier version
+    restored: u64, // Trashed properties brought back to the live listings as they were
+    deleted: u64,  // Properties uploaded or restored after the rollback point, moved to the trash
+    skipped: u64,  // Earlier versions left alone because some of their image bytes are no longer stored
+}
+
+// Function to put every property back to its state as of `timestamp` using the version history, restricted
+// to the admin. Only what versions capture is reverted: purged properties stay gone and favorites, collections,
+// and settings are left as they are. Refuses without changing anything if a property changed after `timestamp`
+// but its retained history doesn't reach back that far.
+#[update]
+fn rollback_to(timestamp: u64) -> Result<RollbackReport, PlatformError> {
+    require_admin();
+    require_sole_approval();
+
+    roll_back(timestamp)
+}
+
+// Helper function that rolls the properties back, for `rollback_to` and approved proposals
+fn roll_back(timestamp: u64) -> Result<RollbackReport, PlatformError> {
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+
+        // Work out what each property should become first, so a refusal leaves everything untouched
+        let ids: BTreeSet<String> = platform.properties.keys().chain(platform.trash.keys().cloned()).collect();
+        let mut plan: Vec<(String, Option<Property>)> = Vec::new();
+        for id in ids {
+            let current = platform
+                .properties
+                .get(&id)
+                .or_else(|| platform.trash.get(&id).cloned())
+                .expect("ID comes from the live or trashed properties");
+            let is_live = platform.properties.contains_key(&id);
+            let times = platform.version_times.get(&id);
+            let deleted_after = |since: u64| {
+                platform
+                    .deletions
+                    .iter()
+                    .any(|(deleted_id, deleted_at)| *deleted_id == id && *deleted_at > since)
+            };
+            // Without recorded times, only the last update time can tell whether anything changed
+            let changed_after = times
+                .and_then(|times| times.last())
+                .is_some_and(|recorded_at| *recorded_at > timestamp)
+                || deleted_after(timestamp)
+                || (times.is_none() && current.updated_at > timestamp);
+            if !changed_after {
+                continue;
+            }
+
+            let target = match platform.version_as_of(&id, timestamp) {
+                // Deleted between that version and the rollback point
+                Some((recorded_at, _))
+                    if platform.deletions.iter().any(|(deleted_id, deleted_at)| {
+                        *deleted_id == id && *deleted_at > recorded_at && *deleted_at <= timestamp
+                    }) =>
+                {
+                    None
+                }
+                Some((_, version)) => Some(version.clone()),
+                None if current.created_at > timestamp => None,
+                None => {
+                    let reach = times.and_then(|times| times.first());
+                    return Err(PlatformError::Conflict(match reach {
+                        Some(oldest) => format!("The history of {} only reaches back to {}.", id, oldest),
+                        None => format!("There is no history for {} to roll back to.", id),
+                    }));
+                }
+            };
+            // Nothing to do for a property that should be gone and already is
+            if target.is_some() || is_live {
+                plan.push((id, target));
+            }
+        }
+
+        let now = env::time();
+        let mut report = RollbackReport::default();
+        for (id, target) in plan {
+            let Some(mut version) = target else {
+                move_to_trash(&mut platform, &id);
+                platform.record_event(AuditEventKind::RolledBack { to: timestamp }, &id);
+                report.deleted += 1;
+                continue;
+            };
+            if version.stored_hashes().any(|hash| !platform.images.contains_key(hash)) {
+                report.skipped += 1;
+                continue;
+            }
+            let current = platform.properties.get(&id);
+            let was_live = current.is_some();
+            if current.is_some_and(|current| candid::encode_one(&version).ok() == candid::encode_one(&current).ok()) {
+                continue;
+            }
+            // Take the version's image references before dropping the current ones, so shared images survive
+            for hash in version.stored_hashes() {
+                if let Some((_, refs)) = platform.images.get_mut(hash) {
+                    *refs += 1;
+                }
+            }
+            version.updated_at = now;
+            let replaced = match platform.trash.remove(&id) {
+                Some(trashed) => Some(trashed),
+                None => platform.properties.remove(&id),
+            };
+            for hash in replaced.iter().flat_map(Property::stored_hashes) {
+                platform.release_image(hash);
+            }
+            platform.properties.insert(id.clone(), version);
+            platform.record_event(AuditEventKind::RolledBack { to: timestamp }, &id);
+            if was_live {
+                report.changed += 1;
+            } else {
+                report.restored += 1;
+            }
+        }
+        Ok(report)
+    })
+}
+
+// Query function to get every property held by the given owner, looked up through the owner index.
+// Expired listings are left out, except for the owner themselves, who may still want to renew them.
+#[query]
+fn get_properties_by_owner(owner: Principal, verified_only: Option<bool>) -> Vec<Property> {
+    let property_ids: Vec<String> = OWNER_INDEX.with(|index| {
+        let index = index.borrow();
+        index.owner_index.get(&owner).map(|ids| ids.iter().cloned().collect()).unwrap_or_default()
+    });
+    let caller = env::caller();
+    let now = env::time();
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        sorted_property_ids(&property_ids)
+            .into_iter()
+            .filter_map(|id| platform.properties.get(id))
+            .filter(|property| property.is_visible_to(caller))
+            .filter(|property| passes_verified_filter(property, verified_only))
+            .filter_map(|property| {
+                if caller == owner {
+                    Some(property.view(now))
+                } else {
+                    property.listed_view(now)
+                }
+            })
+            .collect()
+    })
+}
+
+// Query function to list every owner with how many properties they hold, largest holders first, counted from
+// the owner index
+#[query]
+fn list_owners() -> Vec<(Principal, u64)> {
+    OWNER_INDEX.with(|index| {
+        let index = index.borrow();
+        let mut owners: Vec<(Principal, u64)> =
+            index.owner_index.iter().map(|(owner, ids)| (*owner, ids.len() as u64)).collect();
+        // Ties are broken by principal bytes so the order is stable
+        owners.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.as_slice().cmp(b.0.as_slice())));
+        owners
+    })
+}
+
+// Query function to list how many bytes each owner's live and trashed properties take up, largest first,
+// restricted to the admin
+#[query]
+fn storage_by_owner() -> Vec<(Principal, u64)> {
+    require_admin();
+
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        let mut usage: HashMap<Principal, u64> = HashMap::new();
+        for property in platform.properties.values().chain(platform.trash.values().cloned()) {
+            *usage.entry(property.owner).or_default() += storage_bytes(&platform, &property);
+        }
+        let mut owners: Vec<(Principal, u64)> = usage.into_iter().collect();
+        owners.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.as_slice().cmp(b.0.as_slice())));
+        owners
+    })
+}
+
+// Query function to get how many bytes one owner's live and trashed properties take up, restricted to the admin
+#[query]
+fn storage_for_owner(owner: Principal) -> u64 {
+    require_admin();
+
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        platform
+            .properties
+            .values()
+            .chain(platform.trash.values().cloned())
+            .filter(|property| property.owner == owner)
+            .map(|property| storage_bytes(&platform, &property))
+            .sum()
+    })
+}
+
+// Helper function to estimate the bytes a property takes up: its stored images plus its encoded record.
+// Images shared between properties count in full for each of them.
+fn storage_bytes(platform: &DecentralizedPlatform, property: &Property) -> u64 {
+    let image_bytes: u64 = property
+        .stored_hashes()
+        .filter_map(|hash| platform.images.get(hash))
+        .map(|(image_data, _)| image_data.len() as u64)
+        .sum();
+    image_bytes + response_size_estimate(property) as u64
+}
+
+// Query function to get the properties held by the caller
+#[query]
+fn get_my_properties() -> Vec<Property> {
+    get_properties_by_owner(env::caller(), None)
+}
+
+// Query function to get every property of the given type (`Other` labels are normalized as on upload)
+#[query]
+fn get_properties_by_type(property_type: PropertyType, verified_only: Option<bool>) -> Vec<Property> {
+    let property_type = normalize_property_type(property_type.clone()).unwrap_or(property_type);
+    let caller = env::caller();
+    let now = env::time();
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        platform
+            .properties
+            .values()
+            .filter(|property| property.property_type == property_type)
+            .filter(|property| property.is_visible_to(caller))
+            .filter(|property| passes_verified_filter(property, verified_only))
+            .filter_map(|property| property.listed_view(now))
+            .collect()
+    })
+}
+
+// Query function to find properties whose ID, type, tags, or description contain the query, with a relevance
+// score for each, best matches first; equal scores are ordered by ID
+#[query]
+fn search_properties(query: String, verified_only: Option<bool>) -> Vec<(Property, f32)> {
+    let query = query.trim().to_lowercase();
+    if query.is_empty() {
+        return Vec::new();
+    }
+
+    let caller = env::caller();
+    let now = env::time();
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        let mut results: Vec<(Property, f32)> = platform
+            .properties
+            .values()
+            .filter(|property| property.is_visible_to(caller))
+            .filter(|property| passes_verified_filter(property, verified_only))
+            .filter_map(|property| property.listed_view(now))
+            .map(|property| {
+                let score = search_score(&property, &query);
+                (property, score)
+            })
+            .filter(|(_, score)| *score > 0.0)
+            .collect();
+        // The list is already in ID order and the sort is stable, so ties keep it
+        results.sort_by(|a, b| b.1.total_cmp(&a.1));
+        results
+    })
+}
+
+// Helper function to score how well a property matches a lowercase search query; zero means no match.
+// An exact ID match outranks everything, type and tag matches outrank description ones, and a description
+// match counts for more the closer it is to the start.
+fn search_score(property: &Property, query: &str) -> f32 {
+    let mut score = 0.0;
+    if property.id == query {
+        score += 100.0;
+    }
+    let label = type_label(&property.property_type).trim().to_lowercase();
+    if label == query {
+        score += 10.0;
+    } else if label.contains(query) {
+        score += 6.0;
+    }
+    if property.tags.iter().any(|tag| tag == query) {
+        score += 8.0;
+    } else if property.tags.iter().any(|tag| tag.contains(query)) {
+        score += 5.0;
+    }
+    let description = property.description.to_lowercase();
+    if let Some(position) = description.find(query) {
+        score += 1.0 + 2.0 * (1.0 - position as f32 / description.len() as f32);
+    }
+    score
+}
+
+// Query function to get all properties carrying the given tag
+#[query]
+fn get_properties_by_tag(tag: String, verified_only: Option<bool>) -> Vec<Property> {
+    let tag = normalize_tag(&tag);
+    let caller = env::caller();
+    let now = env::time();
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        platform
+            .properties
+            .values()
+            .filter(|property| property.tags.contains(&tag))
+            .filter(|property| property.is_visible_to(caller))
+            .filter(|property| passes_verified_filter(property, verified_only))
+            .filter_map(|property| property.listed_view(now))
+            .collect()
+    })
+}
+
+// Query function to list every tag in use along with how many properties carry it, sorted by tag
+#[query]
+fn list_all_tags() -> Vec<(String, u64)> {
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        let mut counts: BTreeMap<String, u64> = BTreeMap::new();
+        for tag in platform.properties.values().flat_map(|property| property.tags) {
+            *counts.entry(tag).or_insert(0) += 1;
+        }
+        counts.into_iter().collect()
+    })
+}
+
+// Query function to find located properties within `radius_km` of a point, nearest first
+#[query]
+fn properties_within(lat: f64, lng: f64, radius_km: f64) -> Vec<Property> {
+    let center = GeoLocation { lat, lng, address: None };
+    if !center.is_valid() || radius_km.is_nan() || radius_km < 0.0 {
+        return Vec::new();
+    }
+
+    let caller = env::caller();
+    let now = env::time();
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        let mut nearby: Vec<(f64, Property)> = platform
+            .properties
+            .values()
+            .filter(|property| property.is_visible_to(caller))
+            .filter_map(|property| property.listed_view(now))
+            .filter_map(|property| {
+                let distance = property.location.as_ref()?.distance_km(lat, lng);
+                (distance <= radius_km).then_some((distance, property))
+            })
+            .collect();
+        nearby.sort_by(|a, b| a.0.total_cmp(&b.0).then_with(|| a.1.id.cmp(&b.1.id)));
+        nearby.into_iter().map(|(_, property)| property).collect()
+    })
+}
+
+// Helper function to put property IDs in canonical (lexicographic) order. List queries go through it so their
+// output doesn't depend on HashMap iteration order, which varies with insertion history.
+fn sorted_property_ids<'a>(ids: impl IntoIterator<Item = &'a String>) -> Vec<&'a String> {
+    let mut ids: Vec<&String> = ids.into_iter().collect();
+    ids.sort();
+    ids
+}
+
+// Helper function for the optional `verified_only` filter shared by the listing queries
+fn passes_verified_filter(property: &Property, verified_only: Option<bool>) -> bool {
+    !verified_only.unwrap_or(false) || property.verified
+}
+
+// Query function to get the number of listed properties
+#[query]
+fn property_count() -> u64 {
+    DECENTRALIZED_PLATFORM.with(|platform| platform.borrow().properties.len() as u64)
+}
+
+// Query function to get the number of listed properties of the given type
+#[query]
+fn property_count_by_type(property_type: PropertyType) -> u64 {
+    let property_type = normalize_property_type(property_type.clone()).unwrap_or(property_type);
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        platform
+            .properties
+            .values()
+            .filter(|property| property.property_type == property_type)
+            .count() as u64
+    })
+}
+
+// Query function to count the live properties created in each UTC day between `start` (inclusive) and `end`
+// (exclusive), as (day start, count) in nanoseconds, oldest first. Days without uploads are left out, and an
+// inverted range yields nothing.
+#[query]
+fn uploads_per_day(start: u64, end: u64) -> Vec<(u64, u64)> {
+    if start >= end {
+        return Vec::new();
+    }
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        let mut buckets: BTreeMap<u64, u64> = BTreeMap::new();
+        for property in platform.properties.values() {
+            if (start..end).contains(&property.created_at) {
+                let day_start = property.created_at - property.created_at % DAY_NANOS;
+                *buckets.entry(day_start).or_default() += 1;
+            }
+        }
+        buckets.into_iter().collect()
+    })
+}
+
+// Query function to get platform-wide statistics; an empty platform reports zeros and no latest upload
+#[query]
+fn get_stats() -> PlatformStats {
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+
+        let mut per_type: BTreeMap<String, u64> = BTreeMap::new();
+        let mut owners: HashSet<Principal> = HashSet::new();
+        let mut latest_upload_at: Option<u64> = None;
+        for property in platform.properties.values() {
+            *per_type.entry(type_label(&property.property_type)).or_default() += 1;
+            owners.insert(property.owner);
+            latest_upload_at = latest_upload_at.max(Some(property.created_at));
+        }
+
+        PlatformStats {
+            total_properties: platform.properties.len() as u64,
+            per_type_counts: per_type.into_iter().collect(),
+            unique_owners: owners.len() as u64,
+            total_image_bytes: platform.images.values().map(|(image_data, _)| image_data.len() as u64).sum(),
+            latest_upload_at,
+        }
+    })
+}
+
+// Query function to get every property type label in use, sorted, for building category filters.
+// Labels are normalized again so records saved before normalization don't show up as separate categories.
+#[query]
+fn distinct_types() -> Vec<String> {
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let labels: BTreeSet<String> = platform
+            .borrow()
+            .properties
+            .values()
+            .filter_map(|property| normalize_property_type(property.property_type.clone()).ok())
+            .map(|property_type| type_label(&property_type))
+            .collect();
+        labels.into_iter().collect()
+    })
 }
 
-#[derive(Default)]
-struct DecentralizedPlatform {
-    properties: HashMap<String, Property>,
+// Helper function to build the Merkle leaves of all listed properties, sorted by property ID
+// so the tree doesn't depend on insertion order
+fn merkle_leaves(platform: &DecentralizedPlatform) -> Vec<(String, Hash)> {
+    let mut leaves: Vec<(String, Hash)> = platform
+        .properties
+        .values()
+        .map(|property| {
+            let primary_hash = property.image_hashes.first().map(String::as_str).unwrap_or_default();
+            (property.id.clone(), merkle::leaf_hash(&property.id, primary_hash))
+        })
+        .collect();
+    leaves.sort_by(|a, b| a.0.cmp(&b.0));
+    leaves
 }
 
-// Global thread-safe state using thread-local storage and RwLock
-thread_local! {
-    static DECENTRALIZED_PLATFORM: RwLock<DecentralizedPlatform> = RwLock::new(DecentralizedPlatform::default());
+// Query function to get the hex Merkle root over every listed property's ID and primary image hash
+#[query]
+fn properties_merkle_root() -> String {
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let leaves: Vec<Hash> = merkle_leaves(&platform.borrow()).into_iter().map(|(_, leaf)| leaf).collect();
+        hex::encode(merkle::root(&leaves))
+    })
 }
 
-#[init]
-fn init() {
-    // Initialization logic can be added if necessary
+// Query function to get the hex sibling hashes proving one property is included in `properties_merkle_root`
+#[query]
+fn merkle_proof(property_id: String) -> Option<Vec<String>> {
+    let property_id = normalize_id(&property_id);
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let leaves = merkle_leaves(&platform.borrow());
+        let index = leaves.iter().position(|(id, _)| *id == property_id)?;
+        let hashes: Vec<Hash> = leaves.into_iter().map(|(_, leaf)| leaf).collect();
+        Some(merkle::proof(&hashes, index).iter().map(hex::encode).collect())
+    })
 }
 
-// Function to upload an image, hash it, and store property data
+// Query function to get the canister's own principal and the `Property` schema version it serves
+#[query]
+fn canister_info() -> CanisterInfo {
+    CanisterInfo {
+        canister_id: env::id(),
+        schema_version: SCHEMA_VERSION,
+        property_count: DECENTRALIZED_PLATFORM.with(|platform| platform.borrow().properties.len() as u64),
+    }
+}
+
+// Query function to get the total uncompressed image size of every live property. Images shared by several
+// properties count once per property, unlike the deduplicated figures of `get_stats`.
+#[query]
+fn total_stored_bytes() -> u64 {
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        platform
+            .borrow()
+            .properties
+            .values()
+            .fold(0u64, |total, property| total.saturating_add(property.image_size_bytes))
+    })
+}
+
+// Query function to get the canister's cycles balance and memory use alongside a few platform counts
+#[query]
+fn canister_status_summary() -> CanisterHealth {
+    const WASM_PAGE_BYTES: u64 = 64 * 1024;
+    #[cfg(target_arch = "wasm32")]
+    let heap_bytes = core::arch::wasm32::memory_size::<0>() as u64 * WASM_PAGE_BYTES;
+    #[cfg(not(target_arch = "wasm32"))]
+    let heap_bytes = 0;
+
+    let (property_count, stored_image_bytes) = DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        let image_bytes = platform.images.values().map(|(image_data, _)| image_data.len() as u64).sum();
+        (platform.properties.len() as u64, image_bytes)
+    });
+
+    CanisterHealth {
+        cycles_balance: env::canister_balance(),
+        heap_bytes,
+        stable_memory_bytes: ic_cdk::api::stable::stable_size() * WASM_PAGE_BYTES,
+        property_count,
+        stored_image_bytes,
+    }
+}
+
+// Query function to find every property whose image has the given hash
+#[query]
+fn get_properties_by_hash(image_hash: String) -> Result<Vec<Property>, PlatformError> {
+    let image_hash = parse_hash(&image_hash)?;
+    let caller = env::caller();
+    let now = env::time();
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        Ok(platform
+            .properties
+            .values()
+            .filter(|property| property.image_hashes.contains(&image_hash))
+            .filter(|property| property.is_visible_to(caller))
+            .filter_map(|property| property.listed_view(now))
+            .collect())
+    })
+}
+
+// Helper function to check that a hash argument is a hex digest of a supported length, lowercasing it to match
+// the stored form
+fn parse_hash(s: &str) -> Result<String, PlatformError> {
+    let s = s.trim();
+    let lengths = [HashAlgo::Sha256, HashAlgo::Sha512, HashAlgo::Blake3].map(HashAlgo::hex_len);
+    if !lengths.contains(&s.len()) {
+        return Err(PlatformError::InvalidInput(format!(
+            "Hashes are 64 or 128 hex characters, not {}.",
+            s.len()
+        )));
+    }
+    if !s.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return Err(PlatformError::InvalidInput("Hashes may only contain hex digits.".to_string()));
+    }
+    Ok(s.to_ascii_lowercase())
+}
+
+// Query function to get a short prefix of a property's primary image hash for display and deep links.
+// `len` is clamped to 1..=64 hex characters.
+#[query]
+fn short_hash(property_id: String, len: u8) -> Option<String> {
+    let property_id = normalize_id(&property_id);
+    let len = usize::from(len.clamp(1, 64));
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        let property = platform.properties.get(&property_id).filter(|property| property.is_visible_to(env::caller()))?;
+        let primary_hash = property.image_hashes.first()?;
+        Some(primary_hash.chars().take(len).collect())
+    })
+}
+
+// Query function to get the original bytes of a property's primary image, with its encryption details
+#[query]
+fn get_image(property_id: String) -> Option<ImageContent> {
+    let property_id = normalize_id(&property_id);
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        let property = platform.properties.get(&property_id).filter(|property| property.is_visible_to(env::caller()))?;
+        Some(ImageContent {
+            image_data: platform.image_data(property.image_hashes.first()?)?,
+            encrypted: property.encrypted,
+            key_id: property.key_id.clone(),
+        })
+    })
+}
+
+// Query function to get the bytes of a property's thumbnail, if one was uploaded
+#[query]
+fn get_thumbnail(property_id: String) -> Option<Vec<u8>> {
+    let property_id = normalize_id(&property_id);
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        let property = platform.properties.get(&property_id).filter(|property| property.is_visible_to(env::caller()))?;
+        platform.image_data(property.thumbnail_hash.as_ref()?)
+    })
+}
+
+// Query function to get the original bytes of any stored image by its hash
+#[query]
+fn get_image_by_hash(image_hash: String) -> Result<Option<Vec<u8>>, PlatformError> {
+    let image_hash = parse_hash(&image_hash)?;
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        Ok(platform.image_data(&image_hash))
+    })
+}
+
+// Query function to check supplied bytes against the image hashes stored for a property
+#[query]
+fn verify_image(property_id: String, image_data: Vec<u8>) -> bool {
+    DECENTRALIZED_PLATFORM.with(|platform| image_matches(&platform.borrow(), &property_id, &image_data))
+}
+
+// Query function to check several payloads at once, each against the image hashes of the property it names.
+// Results keep the order of `items`; unknown IDs come back false.
+#[query]
+fn verify_images_batch(items: Vec<(String, Vec<u8>)>) -> Vec<(String, bool)> {
+    if items.len() > MAX_VERIFY_BATCH_LEN {
+        env::trap(&format!("At most {} images can be verified at once.", MAX_VERIFY_BATCH_LEN));
+    }
+    if items.iter().map(|(_, image_data)| image_data.len()).sum::<usize>() > MAX_VERIFY_BATCH_BYTES {
+        env::trap(&format!("At most {} bytes can be verified at once.", MAX_VERIFY_BATCH_BYTES));
+    }
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        items
+            .into_iter()
+            .map(|(property_id, image_data)| {
+                let matches = image_matches(&platform, &property_id, &image_data);
+                (property_id, matches)
+            })
+            .collect()
+    })
+}
+
+// Helper function to check whether bytes hash to one of the stored image hashes of a property
+fn image_matches(platform: &DecentralizedPlatform, property_id: &str, image_data: &[u8]) -> bool {
+    let property_id = normalize_id(property_id);
+    platform.properties.get(&property_id).is_some_and(|property| {
+        property
+            .image_hashes
+            .contains(&hash_image(image_data, property.hash_algo, platform.hash_salt()))
+    })
+}
+
+// Function to check that an image kept in a separate asset canister still matches one of a property's image hashes.
+// Large assets are fetched chunk by chunk; only the unencoded ("identity") version is compared.
 #[update]
-fn upload_property(
-    property_id: String,
-    property_type: PropertyType,
-    image_data: Vec<u8>,
-    description: String,
-    owner: String,
-) -> String {
-    // Validate image data
+async fn attest_external_image(property_id: String, asset_canister: Principal, key: String) -> Result<bool, PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let property_id = normalize_id(&property_id);
+    let (image_hashes, hash_algo, salt) = with_state(|platform| {
+        let property = platform.properties.get(&property_id).ok_or(PlatformError::NotFound)?;
+        Ok((property.image_hashes.clone(), property.hash_algo, platform.hash_salt().to_vec()))
+    })?;
+
+    let unreachable = |(code, message): (ic_cdk::api::call::RejectionCode, String)| PlatformError::CallFailed {
+        message: format!("{:?}: {}", code, message),
+    };
+    let args = AssetGetArgs {
+        key: key.clone(),
+        accept_encodings: vec!["identity".to_string()],
+    };
+    let (asset,): (AssetEncodedContent,) = ic_cdk::call(asset_canister, "get", (args,)).await.map_err(unreachable)?;
+
+    // Refuse to buffer more than any image this canister could have hashed
+    let total_length = u64::try_from(asset.total_length.0).unwrap_or(u64::MAX);
+    if total_length > MAX_ATTESTED_IMAGE_BYTES {
+        return Ok(false);
+    }
+    let mut image_data = asset.content;
+    let mut index = 1u64;
+    while (image_data.len() as u64) < total_length {
+        let chunk_args = AssetGetChunkArgs {
+            key: key.clone(),
+            content_encoding: asset.content_encoding.clone(),
+            index: Nat::from(index),
+            sha256: asset.sha256.clone(),
+        };
+        let (chunk,): (AssetChunk,) =
+            ic_cdk::call(asset_canister, "get_chunk", (chunk_args,)).await.map_err(unreachable)?;
+        if chunk.content.is_empty() {
+            break;
+        }
+        image_data.extend(chunk.content);
+        index += 1;
+    }
     if image_data.is_empty() {
-        ic_cdk::trap("Image data is empty.");
+        return Err(PlatformError::Conflict("The asset is empty.".to_string()));
     }
 
-    // Hash the image data using SHA-256
-    let hash = hash_image(&image_data);
+    Ok(image_hashes.contains(&hash_image(&image_data, hash_algo, &salt)))
+}
 
-    // Create a new Property with the given ID, type, and other details
-    let property = Property {
-        id: property_id.clone(),
-        property_type,
-        image_hash: hash.clone(),
-        description,
-        owner,
-    };
+// HTTP entry point used by the gateway; serves Prometheus-style metrics at /metrics and each property's
+// primary image at /image/{property_id}, so listings can be embedded with a plain <img> tag
+#[query]
+fn http_request(req: HttpRequest) -> HttpResponse {
+    let path = req.url.split('?').next().unwrap_or_default();
+    if let Some(property_id) = path.strip_prefix("/image/") {
+        return image_response(property_id);
+    }
+    match path {
+        "/metrics" => HttpResponse {
+            status_code: 200,
+            headers: vec![("Content-Type".to_string(), "text/plain; version=0.0.4".to_string())],
+            body: render_metrics().into_bytes(),
+            streaming_strategy: None,
+        },
+        _ => not_found_response(),
+    }
+}
+
+// Query function called by the HTTP gateway for each further chunk of a streamed image.
+// An empty final chunk is returned if the image was removed or hidden after streaming started.
+#[query]
+fn http_request_streaming_callback(token: StreamingCallbackToken) -> StreamingCallbackHttpResponse {
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        let still_listed = platform.properties.get(&token.property_id).is_some_and(|property| {
+            property.is_visible_to(env::caller()) && property.image_hashes.contains(&token.image_hash)
+        });
+        match platform.image_data(&token.image_hash).filter(|_| still_listed) {
+            Some(image_data) => {
+                let (body, token) = image_chunk(&token.property_id, &token.image_hash, &image_data, token.index);
+                StreamingCallbackHttpResponse { body, token }
+            }
+            None => StreamingCallbackHttpResponse {
+                body: Vec::new(),
+                token: None,
+            },
+        }
+    })
+}
 
-    // Safely store the property in the decentralized platform
+// Helper function to serve a property's primary image with a Content-Type matching its detected format
+fn image_response(property_id: &str) -> HttpResponse {
+    let property_id = normalize_id(property_id);
     DECENTRALIZED_PLATFORM.with(|platform| {
-        let mut platform = platform.write().expect("Failed to acquire write lock");
-        platform.properties.insert(property_id, property);
+        let platform = platform.borrow();
+        let image = platform
+            .properties
+            .get(&property_id)
+            .filter(|property| property.is_visible_to(env::caller()))
+            .and_then(|property| {
+                let image_hash = property.image_hashes.first()?.clone();
+                Some((property.format, platform.image_data(&image_hash)?, image_hash))
+            });
+        let Some((format, image_data, image_hash)) = image else {
+            return not_found_response();
+        };
+
+        let content_type = match format {
+            Some(ImageFormat::Png) => "image/png",
+            Some(ImageFormat::Jpeg) => "image/jpeg",
+            Some(ImageFormat::Gif) => "image/gif",
+            Some(ImageFormat::WebP) => "image/webp",
+            None => "application/octet-stream",
+        };
+        let (body, token) = image_chunk(&property_id, &image_hash, &image_data, 0);
+        HttpResponse {
+            status_code: 200,
+            headers: vec![("Content-Type".to_string(), content_type.to_string())],
+            body,
+            streaming_strategy: token.map(|token| StreamingStrategy::Callback {
+                callback: StreamingCallback::new(env::id(), "http_request_streaming_callback".to_string()),
+                token,
+            }),
+        }
+    })
+}
+
+// Helper function to cut chunk `index` out of an image, along with the token for the next chunk if any remain
+fn image_chunk(
+    property_id: &str,
+    image_hash: &str,
+    image_data: &[u8],
+    index: u64,
+) -> (Vec<u8>, Option<StreamingCallbackToken>) {
+    let start = usize::try_from(index)
+        .unwrap_or(usize::MAX)
+        .saturating_mul(HTTP_CHUNK_BYTES)
+        .min(image_data.len());
+    let end = start.saturating_add(HTTP_CHUNK_BYTES).min(image_data.len());
+    let token = (end < image_data.len()).then(|| StreamingCallbackToken {
+        property_id: property_id.to_string(),
+        image_hash: image_hash.to_string(),
+        index: index + 1,
     });
+    (image_data[start..end].to_vec(), token)
+}
 
-    // Return the image hash
-    hash
+// Helper function to build the plain-text 404 response
+fn not_found_response() -> HttpResponse {
+    HttpResponse {
+        status_code: 404,
+        headers: vec![("Content-Type".to_string(), "text/plain".to_string())],
+        body: b"Not found".to_vec(),
+        streaming_strategy: None,
+    }
 }
 
-// Helper function to hash image data using SHA-256
-fn hash_image(image_data: &Vec<u8>) -> String {
-    let mut hasher = Sha256::new();
-    hasher.update(image_data);
-    let result = hasher.finalize();
-    hex::encode(result) // Convert the hash to a hexadecimal string
+// Helper function to render platform statistics in the Prometheus text format
+fn render_metrics() -> String {
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+
+        let mut per_type: BTreeMap<String, u64> = BTreeMap::new();
+        for property in platform.properties.values() {
+            *per_type.entry(type_label(&property.property_type)).or_default() += 1;
+        }
+        let image_bytes: u64 = platform.images.values().map(|(image_data, _)| image_data.len() as u64).sum();
+        let admin_present = ADMIN.with(|admin| admin.borrow().is_some());
+
+        let mut metrics = String::new();
+        metrics.push_str("# TYPE bitbuy_properties_total gauge\n");
+        metrics.push_str(&format!("bitbuy_properties_total {}\n", platform.properties.len()));
+        metrics.push_str("# TYPE bitbuy_properties_by_type gauge\n");
+        for (label, count) in per_type {
+            let label = label.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+            metrics.push_str(&format!("bitbuy_properties_by_type{{type=\"{}\"}} {}\n", label, count));
+        }
+        metrics.push_str("# TYPE bitbuy_image_bytes_total gauge\n");
+        metrics.push_str(&format!("bitbuy_image_bytes_total {}\n", image_bytes));
+        metrics.push_str("# TYPE bitbuy_admin_present gauge\n");
+        metrics.push_str(&format!("bitbuy_admin_present {}\n", u8::from(admin_present)));
+        metrics
+    })
+}
+
+// Helper function to split text into lowercase words for keyword matching
+fn keyword_words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+// Helper function to get a display label for a property type, using the inner label for `Other`
+fn type_label(property_type: &PropertyType) -> String {
+    match property_type {
+        PropertyType::Other(label) => label.clone(),
+        known => format!("{:?}", known),
+    }
 }
 
-// Query function to get all property IDs and their associated image hashes
+// Helper function to derive a property's ICRC-7 token ID: the first 16 bytes of SHA-256(property ID)
+fn token_id(property_id: &str) -> u128 {
+    let digest = Sha256::digest(property_id.as_bytes());
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    u128::from_be_bytes(bytes)
+}
+
+// Query function to get the ICRC-7 token ID that represents a property
 #[query]
-fn get_properties() -> Vec<(String, String, String)> {
+fn token_id_of(property_id: String) -> Nat {
+    let property_id = normalize_id(&property_id);
+    Nat::from(token_id(&property_id))
+}
+
+// ICRC-7 query: collection name
+#[query]
+fn icrc7_name() -> String {
+    ICRC7_NAME.to_string()
+}
+
+// ICRC-7 query: collection symbol
+#[query]
+fn icrc7_symbol() -> String {
+    ICRC7_SYMBOL.to_string()
+}
+
+// ICRC-7 query: number of tokens, one per live property
+#[query]
+fn icrc7_total_supply() -> Nat {
+    Nat::from(property_count())
+}
+
+// ICRC-7 query: owner account of each requested token, `None` for unknown tokens
+#[query]
+fn icrc7_owner_of(token_ids: Vec<Nat>) -> Vec<Option<Account>> {
     DECENTRALIZED_PLATFORM.with(|platform| {
-        let platform = platform.read().expect("Failed to acquire read lock");
-        platform
+        let platform = platform.borrow();
+        let owners: HashMap<u128, Principal> = platform
             .properties
+            .values()
+            .map(|property| (token_id(&property.id), property.owner))
+            .collect();
+
+        token_ids
             .iter()
-            .map(|(id, property)| (id.clone(), format!("{:?}", property.property_type), property.image_hash.clone()))
+            .map(|token| {
+                let token = u128::try_from(&token.0).ok()?;
+                owners.get(&token).map(|owner| Account {
+                    owner: *owner,
+                    subaccount: None,
+                })
+            })
             .collect()
     })
 }
 
-// Query function to get a specific property's details by ID
+// ICRC-7 query: tokens held by an account in ascending order, paged with `prev` and `take`
+#[query]
+fn icrc7_tokens_of(account: Account, prev: Option<Nat>, take: Option<Nat>) -> Vec<Nat> {
+    // Owners are plain principals, so only the default subaccount can hold tokens
+    let default_subaccount = account
+        .subaccount
+        .as_ref()
+        .is_none_or(|subaccount| subaccount.iter().all(|byte| *byte == 0));
+    if !default_subaccount {
+        return Vec::new();
+    }
+    // A `prev` beyond the token ID range leaves nothing after it
+    let prev = match prev {
+        Some(prev) => match u128::try_from(&prev.0) {
+            Ok(prev) => Some(prev),
+            Err(_) => return Vec::new(),
+        },
+        None => None,
+    };
+
+    let take = take
+        .and_then(|take| u64::try_from(&take.0).ok())
+        .unwrap_or(MAX_PAGE_LIMIT)
+        .min(MAX_PAGE_LIMIT) as usize;
+    let mut tokens: Vec<u128> = get_properties_by_owner(account.owner, None)
+        .iter()
+        .map(|property| token_id(&property.id))
+        .collect();
+    tokens.sort_unstable();
+
+    tokens
+        .into_iter()
+        .filter(|token| prev.is_none_or(|prev| *token > prev))
+        .take(take)
+        .map(Nat::from)
+        .collect()
+}
+
+// Function to move a property to the trash by its ID, only allowed for its owner
+#[update]
+fn delete_property(property_id: String) -> Result<(), PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let property_id = normalize_id(&property_id);
+    ensure_no_sale_in_flight(&property_id)?;
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let property = platform.properties.get(&property_id).ok_or(PlatformError::NotFound)?;
+        if property.owner != env::caller() {
+            return Err(PlatformError::Unauthorized);
+        }
+        move_to_trash(&mut platform, &property_id);
+        Ok(())
+    })
+}
+
+// Function to trash every property of one owner, allowed for the admin or that owner; returns how many were deleted
+#[update]
+fn delete_properties_by_owner(owner: Principal) -> u64 {
+    require_authenticated();
+    require_not_paused();
+    let caller = env::caller();
+    if caller != owner && !is_admin(caller) {
+        env::trap("Only the admin or the owner can delete these properties.");
+    }
+    // Owners clearing out their own listings don't need the other admins
+    if caller != owner {
+        require_sole_approval();
+    }
+
+    delete_matching(|property| property.owner == owner)
+}
+
+// Function to trash every property of one type, restricted to the admin; returns how many were deleted
+#[update]
+fn delete_properties_by_type(property_type: PropertyType) -> u64 {
+    require_admin();
+    require_sole_approval();
+
+    delete_matching(|property| property.property_type == property_type)
+}
+
+// Helper function to trash all live properties matching a predicate.
+// IDs are collected before anything is removed so the map isn't changed while it is being iterated.
+fn delete_matching(matches: impl Fn(&Property) -> bool) -> u64 {
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let mut property_ids: Vec<String> = platform
+            .properties
+            .values()
+            .filter(|property| matches(property))
+            .map(|property| property.id.clone())
+            .collect();
+        property_ids.sort();
+        for property_id in &property_ids {
+            move_to_trash(&mut platform, property_id);
+        }
+        property_ids.len() as u64
+    })
+}
+
+// Helper function to soft-delete a live property, keeping it in the trash until restored or purged
+fn move_to_trash(platform: &mut DecentralizedPlatform, property_id: &str) {
+    if let Some(mut property) = platform.properties.remove(property_id) {
+        property.updated_at = env::time();
+        platform.deletions.push((property_id.to_string(), property.updated_at));
+        platform.trash.insert(property_id.to_string(), property);
+        remove_from_collections(platform, property_id);
+        platform.record_event(AuditEventKind::Deleted, property_id);
+    }
+}
+
+// Query function to get the tombstones of properties removed from the live listings at or after `timestamp`,
+// oldest first, so indexers polling periodically can drop them. A restored property shows up again as live.
+#[query]
+fn deletions_since(timestamp: u64) -> Vec<(String, u64)> {
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        let start = platform.deletions.partition_point(|(_, deleted_at)| *deleted_at < timestamp);
+        platform.deletions[start..].to_vec()
+    })
+}
+
+// Function to drop the tombstones recorded before `before`, restricted to the admin; meant for once every
+// consumer has synced past that point. Returns how many were dropped.
+#[update]
+fn prune_deletions(before: u64) -> u64 {
+    require_admin();
+    require_sole_approval();
+
+    drop_deletions_before(before)
+}
+
+// Helper function that drops old tombstones, for `prune_deletions` and approved proposals
+fn drop_deletions_before(before: u64) -> u64 {
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let end = platform.deletions.partition_point(|(_, deleted_at)| *deleted_at < before);
+        platform.deletions.drain(..end);
+        end as u64
+    })
+}
+
+// Query function to list the caller's trashed properties, sorted by ID, so they can be restored or purged
+#[query]
+fn get_my_trash() -> Vec<Property> {
+    let caller = env::caller();
+    DECENTRALIZED_PLATFORM.with(|platform| trashed_properties(&platform.borrow(), |property| property.owner == caller))
+}
+
+// Query function to list every trashed property, sorted by ID, restricted to the admin
+#[query]
+fn get_all_trash() -> Vec<Property> {
+    require_admin();
+
+    DECENTRALIZED_PLATFORM.with(|platform| trashed_properties(&platform.borrow(), |_| true))
+}
+
+// Helper function to collect the trashed properties matching a predicate, in ID order
+fn trashed_properties(platform: &DecentralizedPlatform, keep: impl Fn(&Property) -> bool) -> Vec<Property> {
+    sorted_property_ids(platform.trash.keys())
+        .into_iter()
+        .map(|id| &platform.trash[id])
+        .filter(|property| keep(property))
+        .cloned()
+        .collect()
+}
+
+// Function to bring a trashed property back, only allowed for its owner
+#[update]
+fn restore_property(property_id: String) -> Result<(), PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let property_id = normalize_id(&property_id);
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let property = platform.trash.get(&property_id).ok_or(PlatformError::NotFound)?;
+        if property.owner != env::caller() {
+            return Err(PlatformError::Unauthorized);
+        }
+        let mut property = platform.trash.remove(&property_id).expect("Property checked above");
+        property.updated_at = env::time();
+        platform.properties.insert(property_id.clone(), property);
+        platform.record_event(AuditEventKind::Restored, &property_id);
+        Ok(())
+    })
+}
+
+// Function to permanently remove a trashed property and its unshared images, only allowed for its owner
+#[update]
+fn purge_property(property_id: String) -> Result<(), PlatformError> {
+    ensure_authenticated()?;
+    ensure_not_paused()?;
+
+    let property_id = normalize_id(&property_id);
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let property = platform.trash.get(&property_id).ok_or(PlatformError::NotFound)?;
+        if property.owner != env::caller() {
+            return Err(PlatformError::Unauthorized);
+        }
+        let property = platform.trash.remove(&property_id).expect("Property checked above");
+        discard_property(&mut platform, &property);
+        platform.record_event(AuditEventKind::Purged, &property_id);
+        Ok(())
+    })
+}
+
+// Helper function to drop everything else that refers to a property that has been removed for good
+fn discard_property(platform: &mut DecentralizedPlatform, property: &Property) {
+    for image_hash in property.stored_hashes() {
+        platform.release_image(image_hash);
+    }
+    // A removed property can never come back, so it no longer belongs in anyone's favorites
+    platform.favorites.retain(|_, favorites| {
+        favorites.remove(&property.id);
+        !favorites.is_empty()
+    });
+    platform.versions.remove(&property.id);
+    platform.version_times.remove(&property.id);
+    platform.swap_approvals.remove(&property.id);
+    remove_from_collections(platform, &property.id);
+}
+
+// Helper function to take a property out of every collection that lists it
+fn remove_from_collections(platform: &mut DecentralizedPlatform, property_id: &str) {
+    for collection in platform.collections.values_mut() {
+        collection.member_ids.retain(|member_id| member_id != property_id);
+    }
+}
+
+// Query function to list live or trashed properties referencing image bytes that are no longer stored,
+// restricted to the admin. A healthy platform returns an empty list.
 #[query]
-fn get_property_by_id(property_id: String) -> Option<Property> {
+fn orphaned_properties() -> Vec<String> {
+    require_admin();
+
+    DECENTRALIZED_PLATFORM.with(|platform| find_orphaned(&platform.borrow()))
+}
+
+// Function to remove every property reported by `orphaned_properties` for good, restricted to the admin;
+// returns how many were removed
+#[update]
+fn remove_orphaned() -> u64 {
+    require_admin();
+    require_sole_approval();
+
+    remove_orphaned_properties()
+}
+
+// Helper function that removes the orphaned properties, for `remove_orphaned` and approved proposals
+fn remove_orphaned_properties() -> u64 {
     DECENTRALIZED_PLATFORM.with(|platform| {
-        let platform = platform.read().expect("Failed to acquire read lock");
-        platform.properties.get(&property_id).cloned()
+        let mut platform = platform.borrow_mut();
+        let orphaned = find_orphaned(&platform);
+        for property_id in &orphaned {
+            let property = match platform.properties.remove(property_id) {
+                Some(property) => {
+                    platform.deletions.push((property_id.clone(), env::time()));
+                    property
+                }
+                None => platform.trash.remove(property_id).expect("Orphan listed above"),
+            };
+            discard_property(&mut platform, &property);
+            platform.record_event(AuditEventKind::OrphanRemoved, property_id);
+        }
+        orphaned.len() as u64
     })
 }
 
-// Optional: Adding a function to delete a property by its ID (if needed)
+// Helper function to collect the IDs of properties with a missing image, sorted
+fn find_orphaned(platform: &DecentralizedPlatform) -> Vec<String> {
+    let mut orphaned: Vec<String> = platform
+        .properties
+        .values()
+        .chain(platform.trash.values().cloned())
+        .filter(|property| property.stored_hashes().any(|hash| !platform.images.contains_key(hash)))
+        .map(|property| property.id.clone())
+        .collect();
+    orphaned.sort();
+    orphaned
+}
+
+// Query function to count the live properties owned by the anonymous principal, restricted to the admin.
+// Uploads require a signed-in caller, so any such record points at a bug or at data from before that rule.
+#[query]
+fn count_anonymous_owned() -> u64 {
+    require_admin();
+
+    anonymous_owned().len() as u64
+}
+
+// Query function to list the IDs of live properties owned by the anonymous principal, sorted, restricted to the admin
+#[query]
+fn anonymous_owned_ids() -> Vec<String> {
+    require_admin();
+
+    anonymous_owned()
+}
+
+// Function to clean up properties owned by the anonymous principal, restricted to the admin: they are handed to
+// `new_owner` when given, and otherwise removed for good. Returns how many were cleaned up.
+#[update]
+fn clean_up_anonymous_owned(new_owner: Option<Principal>) -> u64 {
+    require_admin();
+    require_sole_approval();
+
+    if new_owner == Some(Principal::anonymous()) {
+        env::trap(ANONYMOUS_REASSIGNMENT);
+    }
+    reassign_anonymous_owned(new_owner)
+}
+
+// Helper function that hands anonymous-owned properties to `new_owner` or removes them, for
+// `clean_up_anonymous_owned` and approved proposals
+fn reassign_anonymous_owned(new_owner: Option<Principal>) -> u64 {
+    let property_ids = anonymous_owned();
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        let now = env::time();
+        for property_id in &property_ids {
+            match new_owner {
+                Some(new_owner) => {
+                    let mut property = platform.properties.get_mut(property_id).expect("Listed by the owner index");
+                    change_owner(&mut property, new_owner, Some("Reassigned by the admin".to_string()), now);
+                    property.commit();
+                    platform.record_event(AuditEventKind::Transferred { to: new_owner }, property_id);
+                }
+                None => {
+                    let property = platform.properties.remove(property_id).expect("Listed by the owner index");
+                    platform.deletions.push((property_id.clone(), now));
+                    discard_property(&mut platform, &property);
+                    platform.record_event(AuditEventKind::Purged, property_id);
+                }
+            }
+        }
+    });
+    property_ids.len() as u64
+}
+
+// Why `clean_up_anonymous_owned` refuses the anonymous principal as the new owner
+const ANONYMOUS_REASSIGNMENT: &str = "Anonymous-owned properties can't be reassigned to the anonymous principal.";
+
+// Helper function to get the sorted IDs of live properties owned by the anonymous principal
+fn anonymous_owned() -> Vec<String> {
+    let mut property_ids: Vec<String> = OWNER_INDEX.with(|index| {
+        let index = index.borrow();
+        index
+            .owner_index
+            .get(&Principal::anonymous())
+            .map(|ids| ids.iter().cloned().collect())
+            .unwrap_or_default()
+    });
+    property_ids.sort();
+    property_ids
+}
+
+// Function to re-hash every live or trashed property whose image bytes are still stored with another algorithm,
+// restricted to the admin; returns how many were converted. Properties missing any bytes keep their old hashes.
+// A converted property loses its metadata signature, since that covered the old primary hash.
 #[update]
-fn delete_property(property_id: String) -> bool {
+fn rehash_all(new_algo: HashAlgo) -> u64 {
+    require_admin();
+    require_sole_approval();
+
+    rehash_properties(new_algo)
+}
+
+// Helper function that re-hashes the properties, for `rehash_all` and approved proposals
+fn rehash_properties(new_algo: HashAlgo) -> u64 {
     DECENTRALIZED_PLATFORM.with(|platform| {
-        let mut platform = platform.write().expect("Failed to acquire write lock");
-        platform.properties.remove(&property_id).is_some()
+        let mut platform = platform.borrow_mut();
+        let salt = platform.hash_salt().to_vec();
+        let mut property_ids: Vec<String> = platform.properties.keys().chain(platform.trash.keys().cloned()).collect();
+        property_ids.sort();
+        let now = env::time();
+        let mut converted = 0;
+        for property_id in property_ids {
+            let in_trash = !platform.properties.contains_key(&property_id);
+            let property = if in_trash {
+                platform.trash[&property_id].clone()
+            } else {
+                platform.properties.get(&property_id).expect("ID comes from the live properties")
+            };
+            if property.hash_algo == new_algo {
+                continue;
+            }
+            // Old hash, new hash, and bytes of every image the property references
+            let Some(rehashed_images) = property
+                .stored_hashes()
+                .map(|old_hash| {
+                    let image_data = platform.image_data(old_hash)?;
+                    Some((old_hash.clone(), hash_image(&image_data, new_algo, &salt), image_data))
+                })
+                .collect::<Option<Vec<_>>>()
+            else {
+                continue;
+            };
+            // Leave the property on its old algorithm rather than mix its bytes up with different stored ones
+            let collides = rehashed_images
+                .iter()
+                .any(|(_, new_hash, image_data)| platform.check_image_bytes(new_hash, image_data).is_err());
+            if collides {
+                continue;
+            }
+
+            let new_hash = |old_hash: &String| {
+                rehashed_images
+                    .iter()
+                    .find(|(old, _, _)| old == old_hash)
+                    .map(|(_, new, _)| new.clone())
+                    .expect("Every stored hash was rehashed")
+            };
+            let mut property = property;
+            property.image_hashes = property.image_hashes.iter().map(new_hash).collect();
+            property.thumbnail_hash = property.thumbnail_hash.as_ref().map(new_hash);
+            property.hash_algo = new_algo;
+            property.signature = None;
+            property.pubkey = None;
+            property.updated_at = now;
+            for (old_hash, new_hash, image_data) in rehashed_images {
+                let was_compressed = platform.compressed_images.contains(&old_hash);
+                platform.retain_image(new_hash.clone(), image_data).expect("Image bytes checked above");
+                if was_compressed {
+                    platform.compress_image(&new_hash);
+                }
+                platform.release_image(&old_hash);
+            }
+            if in_trash {
+                platform.trash.insert(property_id.clone(), property);
+            } else {
+                platform.properties.insert(property_id.clone(), property);
+            }
+            platform.record_event(AuditEventKind::Rehashed { algo: new_algo }, &property_id);
+            converted += 1;
+        }
+        converted
     })
 }
+
+#[cfg(test)]
+mod tests;
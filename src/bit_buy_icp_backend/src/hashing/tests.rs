@@ -0,0 +1,1475 @@
+use super::*;
+
+// Principals the tests act as
+fn admin() -> Principal {
+    Principal::from_slice(&[1; 29])
+}
+
+fn alice() -> Principal {
+    Principal::from_slice(&[2; 29])
+}
+
+fn bob() -> Principal {
+    Principal::from_slice(&[3; 29])
+}
+
+// Install the canister with `admin()` as its admin, leaving `alice()` as the caller
+fn setup() {
+    env::set_caller(admin());
+    init(None);
+    env::set_caller(alice());
+}
+
+// A PNG header followed by `seed`, so each seed gives a different image
+fn png(seed: u8) -> Vec<u8> {
+    let mut image = vec![0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+    image.push(seed);
+    image
+}
+
+// A PNG padded with zeros to `len` bytes
+fn png_of_len(len: usize) -> Vec<u8> {
+    let mut image = png(0);
+    image.resize(len, 0);
+    image
+}
+
+// Upload an art property with one image as the current caller
+fn upload_as_caller(property_id: &str, image: Vec<u8>) -> Result<UploadResult, PlatformError> {
+    upload_of_type(property_id, PropertyType::Art, image)
+}
+
+// Upload a property of the given type with one image as the current caller
+fn upload_of_type(
+    property_id: &str,
+    property_type: PropertyType,
+    image: Vec<u8>,
+) -> Result<UploadResult, PlatformError> {
+    upload_property(
+        property_id.to_string(),
+        property_type,
+        vec![image],
+        format!("Description of {}", property_id),
+        100,
+        None,
+        None,
+    )
+}
+
+// Stored record of a live property
+fn property(property_id: &str) -> Property {
+    DECENTRALIZED_PLATFORM.with(|platform| platform.borrow().properties.get(property_id).expect("Property is live"))
+}
+
+// Stored record, encoded so two reads can be compared byte for byte
+fn stored(property_id: &str) -> Option<Vec<u8>> {
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let property = platform.borrow().properties.get(property_id)?;
+        Some(candid::encode_one(property).unwrap())
+    })
+}
+
+// A second upload under a taken ID is refused and leaves the first record as it was
+#[test]
+fn uploading_an_id_twice_is_rejected() {
+    setup();
+    upload_as_caller("p1", png(1)).unwrap();
+    let first = stored("p1").unwrap();
+
+    env::advance_time(1_000);
+    let second = upload_as_caller("p1", png(2));
+    assert!(matches!(second, Err(PlatformError::DuplicateId { ref id }) if id == "p1"));
+    assert_eq!(stored("p1").unwrap(), first);
+}
+
+// Each algorithm gives a hex digest of its own length, and BLAKE3 matches the reference vectors
+#[test]
+fn hash_algorithms_differ_in_output_length() {
+    let image = png(1);
+    assert_eq!(hash_image(&image, HashAlgo::Sha256, &[]).len(), 64);
+    assert_eq!(hash_image(&image, HashAlgo::Sha512, &[]).len(), 128);
+    assert_eq!(hash_image(&image, HashAlgo::Blake3, &[]).len(), 64);
+    assert_ne!(hash_image(&image, HashAlgo::Sha256, &[]), hash_image(&image, HashAlgo::Blake3, &[]));
+
+    assert_eq!(
+        hash_image(b"", HashAlgo::Blake3, &[]),
+        "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262"
+    );
+    assert_eq!(
+        hash_image(b"abc", HashAlgo::Blake3, &[]),
+        "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"
+    );
+}
+
+// `verify_image` re-hashes with the algorithm the property was uploaded with
+#[test]
+fn verify_image_uses_the_stored_algorithm() {
+    setup();
+    let image = png(1);
+    let algo = Some(HashAlgo::Blake3);
+    upload_property("p1".to_string(), PropertyType::Art, vec![image.clone()], String::new(), 0, algo, None).unwrap();
+    let property = DECENTRALIZED_PLATFORM.with(|platform| platform.borrow().properties.get("p1")).unwrap();
+    assert_eq!(property.hash_algo, HashAlgo::Blake3);
+    assert_eq!(property.image_hashes[0], hash_image(&image, HashAlgo::Blake3, &[]));
+    assert!(verify_image("p1".to_string(), image));
+    assert!(!verify_image("p1".to_string(), png(2)));
+}
+
+// A signature is accepted only over the exact payload and only for the key that made it
+#[test]
+fn metadata_signatures_are_checked() {
+    use ed25519_dalek::{Signer, SigningKey};
+
+    setup();
+    upload_as_caller("p1", png(1)).unwrap();
+    let property = DECENTRALIZED_PLATFORM.with(|platform| platform.borrow().properties.get("p1")).unwrap();
+    let key = SigningKey::from_bytes(&[7; 32]);
+    let other_key = SigningKey::from_bytes(&[8; 32]);
+    let pubkey = key.verifying_key().to_bytes().to_vec();
+
+    let mut tampered = signed_payload(&property);
+    tampered.push(b'!');
+    let tampered_signature = key.sign(&tampered).to_bytes().to_vec();
+    assert!(matches!(
+        sign_metadata("p1".to_string(), pubkey.clone(), tampered_signature),
+        Err(PlatformError::InvalidInput(_))
+    ));
+    let wrong_key_signature = other_key.sign(&signed_payload(&property)).to_bytes().to_vec();
+    assert!(matches!(
+        sign_metadata("p1".to_string(), pubkey.clone(), wrong_key_signature),
+        Err(PlatformError::InvalidInput(_))
+    ));
+    assert!(!verify_metadata_signature("p1".to_string()));
+
+    let signature = key.sign(&signed_payload(&property)).to_bytes().to_vec();
+    sign_metadata("p1".to_string(), pubkey, signature).unwrap();
+    assert!(verify_metadata_signature("p1".to_string()));
+    update_description("p1".to_string(), "Changed".to_string()).unwrap();
+    assert!(!verify_metadata_signature("p1".to_string()));
+}
+
+// Helper function to find the leaf at `path` in a witness, if it wasn't pruned away
+fn witness_leaf(tree: &ic_certified_map::HashTree, path: &[&[u8]]) -> Option<Vec<u8>> {
+    use ic_certified_map::HashTree;
+
+    match (tree, path.split_first()) {
+        (HashTree::Leaf(value), None) => Some(value.to_vec()),
+        (HashTree::Fork(forks), Some(_)) => witness_leaf(&forks.0, path).or_else(|| witness_leaf(&forks.1, path)),
+        (HashTree::Labeled(label, subtree), Some((first, rest))) if label == first => witness_leaf(subtree, rest),
+        _ => None,
+    }
+}
+
+// A witness for one property reconstructs the certified root and carries that property's digest
+#[test]
+fn property_witness_reconstructs_the_certified_root() {
+    setup();
+    for (seed, property_id) in ["p1", "p2", "p3"].iter().enumerate() {
+        upload_as_caller(property_id, png(seed as u8)).unwrap();
+    }
+    let root = env::certified_data();
+    let stored = DECENTRALIZED_PLATFORM.with(|platform| platform.borrow().properties.get("p2")).unwrap();
+    CERTIFIED_DIGESTS.with(|digests| {
+        let digests = digests.borrow();
+        let witness = ic_certified_map::labeled(CERTIFIED_PROPERTIES_LABEL, digests.witness(b"p2"));
+        assert_eq!(witness.reconstruct().to_vec(), root);
+        let leaf = witness_leaf(&witness, &[CERTIFIED_PROPERTIES_LABEL, b"p2"]);
+        assert_eq!(leaf, Some(property_digest(&stored).to_vec()));
+    });
+
+    // Changing a certified field moves the root
+    update_description("p2".to_string(), "Changed".to_string()).unwrap();
+    assert_ne!(env::certified_data(), root);
+    let certified = get_property_by_id("p2".to_string()).unwrap();
+    assert!(certified.witness.starts_with(&[0xd9, 0xd9, 0xf7]));
+}
+
+// A compressible image is stored compressed and comes back byte for byte, with its original hash
+#[test]
+fn compressed_images_round_trip() {
+    setup();
+    let mut image = png(1);
+    image.extend(std::iter::repeat_n(b'a', 4096));
+    let options = UploadOptions {
+        compress: Some(true),
+        ..Default::default()
+    };
+    let images = vec![image.clone()];
+    let result = upload_property("p1".to_string(), PropertyType::Art, images, String::new(), 0, None, Some(options));
+    let hash = result.unwrap().hashes[0].clone();
+    assert_eq!(hash, hash_image(&image, HashAlgo::Sha256, &[]));
+
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        assert!(platform.compressed_images.contains(&hash));
+        assert!(platform.images[&hash].0.len() < image.len());
+        assert_eq!(platform.original_image_len(&hash), Some(image.len() as u64));
+    });
+    assert_eq!(get_image("p1".to_string()).unwrap().image_data, image);
+    assert!(verify_image("p1".to_string(), image));
+}
+
+// Promoting a compressed image to primary reads its format from the original bytes
+#[test]
+fn removing_the_primary_image_detects_the_next_format_when_compressed() {
+    setup();
+    let mut primary = png(1);
+    primary.extend(std::iter::repeat_n(b'a', 4096));
+    let mut next = b"GIF89a".to_vec();
+    next.extend(std::iter::repeat_n(b'b', 4096));
+    let options = UploadOptions {
+        compress: Some(true),
+        ..Default::default()
+    };
+    let images = vec![primary, next];
+    let result = upload_property("p1".to_string(), PropertyType::Art, images, String::new(), 0, None, Some(options));
+    let hashes = result.unwrap().hashes;
+    assert!(DECENTRALIZED_PLATFORM.with(|platform| platform.borrow().compressed_images.contains(&hashes[1])));
+
+    remove_image("p1".to_string(), hashes[0].clone()).unwrap();
+    let property = DECENTRALIZED_PLATFORM.with(|platform| platform.borrow().properties.get("p1")).unwrap();
+    assert_eq!(property.format, Some(ImageFormat::Gif));
+}
+
+// Helper function to fold a Merkle proof back into a root the way a client would: each sibling is hashed
+// with the running node, smaller first, under the 0x01 node prefix
+fn fold_merkle_proof(leaf: Hash, proof: &[String]) -> String {
+    let root = proof.iter().fold(leaf, |node, sibling| {
+        let sibling: Hash = hex::decode(sibling).unwrap().try_into().unwrap();
+        let (left, right) = if node <= sibling { (node, sibling) } else { (sibling, node) };
+        Sha256::new().chain_update([0x01]).chain_update(left).chain_update(right).finalize().into()
+    });
+    hex::encode(root)
+}
+
+// Every property's proof hashes back to the published root, for even and odd leaf counts
+#[test]
+fn merkle_proofs_hash_back_to_the_root() {
+    setup();
+    for count in 1..=5u8 {
+        upload_as_caller(&format!("p{}", count), png(count)).unwrap();
+        let root = properties_merkle_root();
+        for index in 1..=count {
+            let property_id = format!("p{}", index);
+            let property = DECENTRALIZED_PLATFORM.with(|platform| platform.borrow().properties.get(&property_id));
+            let leaf = merkle::leaf_hash(&property_id, &property.unwrap().image_hashes[0]);
+            let proof = merkle_proof(property_id).unwrap();
+            assert_eq!(fold_merkle_proof(leaf, &proof), root, "{} of {} leaves", index, count);
+        }
+    }
+    assert!(merkle_proof("missing".to_string()).is_none());
+}
+
+// While a buyer's payment is in flight the owner can't move, relist or withdraw the listing,
+// and the reservation sweep leaves the buyer's hold in place
+#[test]
+fn owners_cannot_pull_a_listing_while_it_is_being_paid_for() {
+    setup();
+    upload_as_caller("p1", png(1)).unwrap();
+    env::set_caller(bob());
+    reserve_property("p1".to_string()).unwrap();
+    SALES_IN_FLIGHT.with(|sales| sales.borrow_mut().insert("p1".to_string(), env::time()));
+
+    env::set_caller(alice());
+    let conflict = |result: Result<(), PlatformError>| matches!(result, Err(PlatformError::Conflict(_)));
+    assert!(conflict(transfer_property("p1".to_string(), bob(), None).map(drop)));
+    assert!(conflict(cancel_reservation("p1".to_string())));
+    assert!(conflict(set_status("p1".to_string(), ListingStatus::Available)));
+    assert!(conflict(delete_property("p1".to_string())));
+
+    env::advance_time(RESERVATION_TTL_NANOS - 1);
+    sweep_expired_reservations();
+    let property = DECENTRALIZED_PLATFORM.with(|platform| platform.borrow().properties.get("p1").unwrap());
+    assert_eq!(property.reservation.map(|reservation| reservation.reserved_by), Some(bob()));
+
+    SALES_IN_FLIGHT.with(|sales| sales.borrow_mut().remove("p1"));
+    assert!(cancel_reservation("p1".to_string()).is_ok());
+}
+
+// With a threshold of two, a destructive endpoint refuses to run on one admin's say-so, and the
+// same action proposed by one admin runs once a second admin approves it
+#[test]
+fn destructive_actions_wait_for_the_approval_threshold() {
+    setup();
+    upload_as_caller("p1", png(1)).unwrap();
+    env::set_caller(admin());
+    set_admin_council(vec![bob()], 2);
+
+    let direct = std::panic::catch_unwind(|| delete_properties_by_type(PropertyType::Art));
+    assert!(direct.is_err());
+    let proposal_id = propose_action(AdminAction::DeletePropertiesByType {
+        property_type: PropertyType::Art,
+    })
+    .unwrap();
+    assert!(stored("p1").is_some());
+
+    env::set_caller(bob());
+    assert!(approve_action(proposal_id).unwrap());
+    assert!(stored("p1").is_none());
+
+    // Redirecting payments to another ledger is held to the same threshold
+    let ledger = Principal::from_slice(&[9; 10]);
+    env::set_caller(admin());
+    assert!(std::panic::catch_unwind(|| set_ledger_canister(ledger)).is_err());
+    let proposal_id = propose_action(AdminAction::SetLedgerCanister { ledger }).unwrap();
+    DECENTRALIZED_PLATFORM.with(|platform| assert_ne!(platform.borrow().ledger_canister(), ledger));
+    env::set_caller(bob());
+    assert!(approve_action(proposal_id).unwrap());
+    DECENTRALIZED_PLATFORM.with(|platform| assert_eq!(platform.borrow().ledger_canister(), ledger));
+}
+
+// While paused, the batch, chunked-upload, favorite and view endpoints report `Paused` instead of trapping
+#[test]
+fn paused_endpoints_return_an_error() {
+    setup();
+    upload_as_caller("p1", png(1)).unwrap();
+    env::set_caller(admin());
+    set_paused(true);
+
+    env::set_caller(alice());
+    assert!(matches!(upload_properties_batch(Vec::new()), Err(PlatformError::Paused)));
+    assert!(matches!(begin_upload("p2".to_string(), 1), Err(PlatformError::Paused)));
+    assert!(matches!(remove_favorite("p1".to_string()), Err(PlatformError::Paused)));
+    assert!(matches!(record_view("p1".to_string()), Err(PlatformError::Paused)));
+}
+
+// Endpoints returning `Result` report an anonymous caller as `Unauthorized` instead of trapping
+#[test]
+fn anonymous_callers_get_an_unauthorized_error() {
+    setup();
+    upload_as_caller("p1", png(1)).unwrap();
+    delete_property("p1".to_string()).unwrap();
+
+    env::set_caller(Principal::anonymous());
+    assert!(matches!(upload_as_caller("p2", png(2)), Err(PlatformError::Unauthorized)));
+    assert!(matches!(restore_property("p1".to_string()), Err(PlatformError::Unauthorized)));
+    assert!(matches!(purge_property("p1".to_string()), Err(PlatformError::Unauthorized)));
+}
+
+// A backup exported page by page, with image bytes copied separately in pieces, staged after a reset,
+// and imported gives back the same properties, trash, images, and events, and records the import. An image that
+// wasn't fully staged is refused, and forcing an import over live data drops the favorites.
+#[test]
+fn exported_state_round_trips_through_a_chunked_import() {
+    setup();
+    // Larger than one chunk, and than a whole message could carry
+    upload_as_caller("p1", png_of_len(2_500_000)).unwrap();
+    upload_as_caller("p2", png(2)).unwrap();
+    delete_property("p2".to_string()).unwrap();
+
+    env::set_caller(admin());
+    let mut pages = Vec::new();
+    let mut offset = Some(0);
+    while let Some(start) = offset {
+        let page = export_state(start, 1);
+        offset = page.next_offset;
+        pages.push(page.snapshot);
+    }
+    let mut exported = PlatformSnapshot::default();
+    for page in pages.clone() {
+        exported.append(page);
+    }
+    let mut images = Vec::new();
+    for (hash, len) in &exported.images {
+        let mut image_data = Vec::new();
+        while (image_data.len() as u64) < *len {
+            let chunk = export_image_chunk(hash.clone(), image_data.len() as u64, u64::MAX).unwrap();
+            assert!(!chunk.is_empty() && chunk.len() as u64 <= MAX_IMAGE_CHUNK_BYTES);
+            image_data.extend(chunk);
+        }
+        images.push((hash.clone(), image_data));
+    }
+    let stage = |images: &[(String, Vec<u8>)]| {
+        for page in pages.clone() {
+            import_chunk(page).unwrap();
+        }
+        for (hash, image_data) in images {
+            for (index, chunk) in image_data.chunks(MAX_IMAGE_CHUNK_BYTES as usize).enumerate() {
+                import_image_chunk(hash.clone(), index as u64 * MAX_IMAGE_CHUNK_BYTES, chunk.to_vec()).unwrap();
+            }
+        }
+    };
+    let original = stored("p1").unwrap();
+
+    reset_platform();
+    let mut partial = images.clone();
+    partial[0].1.truncate(1);
+    stage(&partial);
+    assert!(matches!(import_state(false), Err(PlatformError::InvalidInput(_))));
+    assert!(stored("p1").is_none());
+
+    stage(&images);
+    import_state(false).unwrap();
+    assert_eq!(stored("p1").unwrap(), original);
+    assert_eq!(get_image("p1".to_string()).unwrap().image_data, png_of_len(2_500_000));
+    let restored = export_state(0, MAX_PAGE_LIMIT).snapshot;
+    assert_eq!(candid::encode_one(&restored.trash).unwrap(), candid::encode_one(&exported.trash).unwrap());
+    assert_eq!(restored.images, exported.images);
+    let (imported_events, new_events) = restored.events.split_at(exported.events.len());
+    assert_eq!(candid::encode_one(imported_events).unwrap(), candid::encode_one(&exported.events).unwrap());
+    assert!(matches!(new_events, [event] if matches!(event.kind, AuditEventKind::StateImported)));
+
+    env::set_caller(alice());
+    add_favorite("p1".to_string()).unwrap();
+    upload_as_caller("p3", png(3)).unwrap();
+    delete_property("p3".to_string()).unwrap();
+    env::set_caller(admin());
+    stage(&images);
+    import_state(true).unwrap();
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        assert!(platform.favorites.is_empty());
+        assert!(platform.transactions.is_empty() && platform.deletions.is_empty());
+    });
+}
+
+// A reset clears the data but keeps the admin and the settings given at install
+#[test]
+fn reset_keeps_install_settings() {
+    env::set_caller(admin());
+    init(Some(InitArgs {
+        hash_salt: Some(b"salt".to_vec()),
+        max_image_bytes: Some(1_000),
+        max_properties_per_owner: Some(3),
+        ..Default::default()
+    }));
+    env::set_caller(alice());
+    upload_as_caller("p1", png(1)).unwrap();
+
+    env::set_caller(admin());
+    reset_platform();
+    reset_platform();
+    assert!(stored("p1").is_none());
+    assert_eq!(get_admin(), Some(admin()));
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        assert_eq!(platform.hash_salt(), b"salt");
+        assert_eq!(platform.max_image_bytes, Some(1_000));
+        assert_eq!(platform.max_properties_per_owner, Some(3));
+        assert!(matches!(platform.events.last().map(|event| &event.kind), Some(AuditEventKind::PlatformReset)));
+    });
+}
+
+// `transfer_all` refuses the whole batch while any of the caller's properties is cooling down
+#[test]
+fn transfer_all_respects_the_transfer_cooldown() {
+    setup();
+    env::set_caller(admin());
+    set_transfer_cooldown(Some(60));
+    env::set_caller(bob());
+    upload_as_caller("p1", png(1)).unwrap();
+    transfer_property("p1".to_string(), alice(), None).unwrap();
+    env::set_caller(alice());
+    upload_as_caller("p2", png(2)).unwrap();
+
+    env::advance_time(20_000_000_000);
+    let refused = transfer_all(bob());
+    assert!(matches!(refused, Err(PlatformError::CooldownActive { retry_after_secs: 40 })));
+    let owner = DECENTRALIZED_PLATFORM.with(|platform| platform.borrow().properties.get("p2").unwrap().owner);
+    assert_eq!(owner, alice());
+
+    env::advance_time(40_000_000_000);
+    assert_eq!(transfer_all(bob()).unwrap(), 2);
+}
+
+// An upload without image bytes is refused with `EmptyImage`, and a valid one returns its hash
+#[test]
+fn empty_images_are_rejected_with_an_error() {
+    setup();
+    assert!(matches!(upload_as_caller("p1", Vec::new()), Err(PlatformError::EmptyImage)));
+    assert!(stored("p1").is_none());
+
+    let result = upload_as_caller("p1", png(1)).unwrap();
+    assert_eq!(result.hashes, vec![hash_image(&png(1), HashAlgo::default(), &[])]);
+    assert_eq!(property("p1").image_hashes, result.hashes);
+}
+
+// Images over the size limit are refused with `TooLarge`, and the admin can lower the limit
+#[test]
+fn oversized_images_are_rejected() {
+    setup();
+    let refused = upload_as_caller("big", png_of_len(6 * 1024 * 1024));
+    assert!(matches!(refused, Err(PlatformError::TooLarge { limit_bytes }) if limit_bytes == MAX_IMAGE_BYTES));
+    assert!(stored("big").is_none());
+    upload_as_caller("small", png_of_len(1024)).unwrap();
+
+    env::set_caller(admin());
+    set_max_image_bytes(512);
+    env::set_caller(alice());
+    let refused = upload_as_caller("small2", png_of_len(1024));
+    assert!(matches!(refused, Err(PlatformError::TooLarge { limit_bytes: 512 })));
+}
+
+// The uploader becomes the owner, and another principal can't delete the property
+#[test]
+fn only_the_owner_can_delete() {
+    setup();
+    upload_as_caller("p1", png(1)).unwrap();
+    assert_eq!(property("p1").owner, alice());
+
+    env::set_caller(bob());
+    assert!(matches!(delete_property("p1".to_string()), Err(PlatformError::Unauthorized)));
+    assert!(stored("p1").is_some());
+
+    env::set_caller(alice());
+    delete_property("p1".to_string()).unwrap();
+    assert!(stored("p1").is_none());
+}
+
+// After a transfer the new owner holds the property, and the old owner can no longer move or delete it
+#[test]
+fn transfers_hand_over_control() {
+    setup();
+    upload_as_caller("p1", png(1)).unwrap();
+    transfer_property("p1".to_string(), bob(), None).unwrap();
+    assert_eq!(property("p1").owner, bob());
+
+    let again = transfer_property("p1".to_string(), admin(), None);
+    assert!(matches!(again, Err(PlatformError::Unauthorized)));
+    assert!(matches!(delete_property("p1".to_string()), Err(PlatformError::Unauthorized)));
+    assert_eq!(property("p1").owner, bob());
+    let missing = transfer_property("p2".to_string(), bob(), None);
+    assert!(matches!(missing, Err(PlatformError::NotFound)));
+}
+
+// The type filter returns exactly the listings of that type, telling `Other` labels apart by their text
+#[test]
+fn properties_filter_by_type() {
+    setup();
+    upload_of_type("house", PropertyType::RealEstate, png(1)).unwrap();
+    upload_of_type("car", PropertyType::Car, png(2)).unwrap();
+    upload_of_type("painting", PropertyType::Art, png(3)).unwrap();
+    upload_of_type("ring", PropertyType::Other("Jewelry".to_string()), png(4)).unwrap();
+    upload_of_type("coin", PropertyType::Other("Coins".to_string()), png(5)).unwrap();
+
+    let ids = |property_type: PropertyType| -> Vec<String> {
+        get_properties_by_type(property_type, None).into_iter().map(|property| property.id).collect()
+    };
+    assert_eq!(ids(PropertyType::RealEstate), ["house"]);
+    assert_eq!(ids(PropertyType::Car), ["car"]);
+    assert_eq!(ids(PropertyType::Art), ["painting"]);
+    assert_eq!(ids(PropertyType::Other("Jewelry".to_string())), ["ring"]);
+    assert_eq!(ids(PropertyType::Other("Coins".to_string())), ["coin"]);
+    assert!(ids(PropertyType::Other("Stamps".to_string())).is_empty());
+}
+
+// Editing the description moves `updated_at` forward and leaves `created_at` as uploaded
+#[test]
+fn edits_refresh_updated_at_only() {
+    setup();
+    let uploaded_at = env::time();
+    upload_as_caller("p1", png(1)).unwrap();
+    let uploaded = property("p1");
+    assert_eq!((uploaded.created_at, uploaded.updated_at), (uploaded_at, uploaded_at));
+
+    env::advance_time(5_000);
+    update_description("p1".to_string(), "A new description".to_string()).unwrap();
+    let edited = get_property_by_id("p1".to_string()).unwrap().property;
+    assert_eq!(edited.created_at, uploaded_at);
+    assert_eq!(edited.updated_at, uploaded_at + 5_000);
+}
+
+// The installing principal becomes the admin, and `require_admin` refuses anyone else
+#[test]
+fn only_the_admin_passes_require_admin() {
+    setup();
+    assert_eq!(get_admin(), Some(admin()));
+    assert!(std::panic::catch_unwind(require_admin).is_err());
+
+    env::set_caller(admin());
+    require_admin();
+}
+
+// `verify_image` accepts the uploaded bytes and rejects tampered bytes or an unknown ID
+#[test]
+fn verify_image_detects_tampering() {
+    setup();
+    upload_as_caller("p1", png(1)).unwrap();
+
+    assert!(verify_image("p1".to_string(), png(1)));
+    let mut tampered = png(1);
+    tampered.push(0);
+    assert!(!verify_image("p1".to_string(), tampered));
+    assert!(!verify_image("p2".to_string(), png(1)));
+}
+
+// The provenance lists the uploader and then each new owner, oldest first
+#[test]
+fn provenance_records_every_owner() {
+    setup();
+    let uploaded_at = env::time();
+    upload_as_caller("p1", png(1)).unwrap();
+    env::advance_time(1_000);
+    transfer_property("p1".to_string(), bob(), None).unwrap();
+    env::advance_time(1_000);
+    env::set_caller(bob());
+    transfer_property("p1".to_string(), admin(), None).unwrap();
+
+    let history: Vec<(Principal, u64)> = get_provenance("p1".to_string())
+        .into_iter()
+        .map(|record| (record.owner, record.acquired_at))
+        .collect();
+    assert_eq!(
+        history,
+        [(alice(), uploaded_at), (bob(), uploaded_at + 1_000), (admin(), uploaded_at + 2_000)]
+    );
+}
+
+// A PNG is accepted and its format stored, while bytes with no known image header are refused
+#[test]
+fn uploads_must_be_recognized_images() {
+    setup();
+    upload_as_caller("p1", png(1)).unwrap();
+    assert!(matches!(property("p1").format, Some(ImageFormat::Png)));
+
+    let refused = upload_as_caller("p2", b"#!/bin/sh\necho hi\n".to_vec());
+    assert!(matches!(refused, Err(PlatformError::InvalidInput(_))));
+    assert!(stored("p2").is_none());
+}
+
+// The property ID, description, and `Other` label are accepted up to their limits and refused past them
+#[test]
+fn text_fields_are_bounded() {
+    setup();
+    let upload_with = |property_id: String, property_type: PropertyType, description: String, seed: u8| {
+        upload_property(property_id, property_type, vec![png(seed)], description, 100, None, None)
+    };
+    let too_long = |result: Result<UploadResult, PlatformError>, field: &str| {
+        matches!(result, Err(PlatformError::InvalidInput(message)) if message.contains(field))
+    };
+
+    let art = || PropertyType::Art;
+    upload_with("a".repeat(MAX_PROPERTY_ID_LEN), art(), String::new(), 1).unwrap();
+    let refused = upload_with("b".repeat(MAX_PROPERTY_ID_LEN + 1), art(), String::new(), 2);
+    assert!(too_long(refused, "property_id"));
+
+    upload_with("p1".to_string(), art(), "d".repeat(MAX_DESCRIPTION_LEN), 3).unwrap();
+    let refused = upload_with("p2".to_string(), art(), "d".repeat(MAX_DESCRIPTION_LEN + 1), 4);
+    assert!(too_long(refused, "description"));
+
+    let label = |len: usize| PropertyType::Other("x".repeat(len));
+    upload_with("p3".to_string(), label(MAX_TYPE_LABEL_LEN), String::new(), 5).unwrap();
+    let refused = upload_with("p4".to_string(), label(MAX_TYPE_LABEL_LEN + 1), String::new(), 6);
+    assert!(too_long(refused, "property_type"));
+}
+
+// Once a principal uses up its hourly uploads it gets `RateLimited` until the oldest one ages out
+#[test]
+fn uploads_are_rate_limited_per_principal() {
+    setup();
+    env::set_caller(admin());
+    set_max_uploads_per_hour(2);
+
+    env::set_caller(alice());
+    upload_as_caller("p1", png(1)).unwrap();
+    env::advance_time(600 * 1_000_000_000);
+    upload_as_caller("p2", png(2)).unwrap();
+    let refused = upload_as_caller("p3", png(3));
+    assert!(matches!(refused, Err(PlatformError::RateLimited { retry_after_secs: 3000 })));
+
+    env::set_caller(bob());
+    upload_as_caller("p4", png(4)).unwrap();
+
+    env::set_caller(alice());
+    env::advance_time(3000 * 1_000_000_000);
+    upload_as_caller("p3", png(3)).unwrap();
+}
+
+// The anonymous principal can't upload or delete, while queries stay open to it
+#[test]
+fn anonymous_callers_cannot_mutate() {
+    setup();
+    upload_as_caller("p1", png(1)).unwrap();
+
+    env::set_caller(Principal::anonymous());
+    assert!(matches!(upload_as_caller("p2", png(2)), Err(PlatformError::Unauthorized)));
+    assert!(matches!(delete_property("p1".to_string()), Err(PlatformError::Unauthorized)));
+    let transfer = transfer_property("p1".to_string(), bob(), None);
+    assert!(matches!(transfer, Err(PlatformError::Unauthorized)));
+    assert!(get_property_by_id("p1".to_string()).is_some());
+    assert!(stored("p2").is_none());
+}
+
+// Reads report a listing as `Expired` once its expiry passes, and `sweep_expired` stores that status
+#[test]
+fn listings_expire_after_their_expiry() {
+    setup();
+    let options = UploadOptions {
+        expires_at: Some(env::time() + 1_000),
+        ..Default::default()
+    };
+    upload_property("p1".to_string(), PropertyType::Art, vec![png(1)], String::new(), 100, None, Some(options))
+        .unwrap();
+    upload_as_caller("p2", png(2)).unwrap();
+    let status = || get_property_by_id("p1".to_string()).unwrap().property.status;
+    assert_eq!(status(), ListingStatus::Available);
+
+    env::advance_time(1_000);
+    assert_eq!(status(), ListingStatus::Expired);
+    assert_eq!(property("p1").status, ListingStatus::Available);
+
+    env::set_caller(admin());
+    assert_eq!(sweep_expired(), 1);
+    assert_eq!(property("p1").status, ListingStatus::Expired);
+    assert_eq!(property("p2").status, ListingStatus::Available);
+    assert_eq!(sweep_expired(), 0);
+}
+
+// Bulk deletes trash every match and report the count, and an owner can clear out only their own
+#[test]
+fn bulk_deletes_by_owner_and_type() {
+    setup();
+    upload_as_caller("a1", png(1)).unwrap();
+    upload_of_type("a2", PropertyType::Car, png(2)).unwrap();
+    env::set_caller(bob());
+    upload_as_caller("b1", png(3)).unwrap();
+    upload_of_type("b2", PropertyType::Car, png(4)).unwrap();
+
+    assert!(std::panic::catch_unwind(|| delete_properties_by_owner(alice())).is_err());
+    assert_eq!(delete_properties_by_owner(bob()), 2);
+    assert!(stored("b1").is_none() && stored("b2").is_none());
+    let trashed: Vec<String> = get_my_trash().into_iter().map(|property| property.id).collect();
+    assert_eq!(trashed, ["b1", "b2"]);
+
+    env::set_caller(admin());
+    assert_eq!(delete_properties_by_type(PropertyType::Car), 1);
+    assert!(stored("a1").is_some() && stored("a2").is_none());
+}
+
+// Only registered verifiers can set the badge, and `verified_only` filters listings down to badged ones
+#[test]
+fn only_verifiers_can_verify() {
+    setup();
+    upload_as_caller("p1", png(1)).unwrap();
+    upload_as_caller("p2", png(2)).unwrap();
+
+    env::set_caller(bob());
+    assert!(std::panic::catch_unwind(|| set_verified("p1".to_string(), true)).is_err());
+    assert!(!property("p1").verified);
+
+    env::set_caller(admin());
+    add_verifier(bob());
+    env::set_caller(bob());
+    set_verified("p1".to_string(), true).unwrap();
+    assert!(property("p1").verified);
+    let verified: Vec<String> = get_properties_by_type(PropertyType::Art, Some(true))
+        .into_iter()
+        .map(|property| property.id)
+        .collect();
+    assert_eq!(verified, ["p1"]);
+    assert_eq!(get_properties_by_type(PropertyType::Art, None).len(), 2);
+}
+
+// `Other` labels are trimmed and lowercased, blank ones refused, and known synonyms mapped to variants
+#[test]
+fn other_labels_are_normalized() {
+    let other = |label: &str| PropertyType::Other(label.to_string());
+    assert_eq!(normalize_property_type(other("  Jewelry ")).unwrap(), other("jewelry"));
+    assert_eq!(normalize_property_type(other("CAR")).unwrap(), PropertyType::Car);
+    assert_eq!(normalize_property_type(other(" Real Estate")).unwrap(), PropertyType::RealEstate);
+    assert!(matches!(normalize_property_type(other("   ")), Err(PlatformError::InvalidInput(_))));
+
+    setup();
+    upload_of_type("p1", other(" Cars "), png(1)).unwrap();
+    assert_eq!(property("p1").property_type, PropertyType::Car);
+}
+
+// IDs are trimmed and lowercased, so "p1", "P1", and " p1 " name the same record, and blank IDs are refused
+#[test]
+fn property_ids_are_normalized() {
+    setup();
+    upload_as_caller(" P1 ", png(1)).unwrap();
+
+    for id in ["p1", "P1", " p1 "] {
+        assert_eq!(get_property_by_id(id.to_string()).unwrap().property.id, "p1");
+    }
+    assert!(matches!(upload_as_caller("p1", png(2)), Err(PlatformError::DuplicateId { .. })));
+    assert!(matches!(upload_as_caller("   ", png(3)), Err(PlatformError::InvalidInput(_))));
+    delete_property("P1".to_string()).unwrap();
+    assert!(stored("p1").is_none());
+}
+
+// With a type allow-list set, uploads of other types are refused
+#[test]
+fn uploads_follow_the_allowed_types() {
+    setup();
+    env::set_caller(admin());
+    set_allowed_types(vec![PropertyType::RealEstate]);
+
+    env::set_caller(alice());
+    let refused = upload_of_type("car", PropertyType::Car, png(1));
+    assert!(matches!(refused, Err(PlatformError::InvalidInput(_))));
+    upload_of_type("house", PropertyType::RealEstate, png(2)).unwrap();
+    assert!(stored("car").is_none());
+}
+
+// A private listing is hidden from principals not on its list until the owner grants them access
+#[test]
+fn private_listings_are_shown_to_listed_principals_only() {
+    setup();
+    let options = UploadOptions {
+        visibility: Some(Visibility::Private(Vec::new())),
+        ..Default::default()
+    };
+    upload_property("p1".to_string(), PropertyType::Art, vec![png(1)], String::new(), 100, None, Some(options))
+        .unwrap();
+    let visible = || get_property_by_id("p1".to_string()).is_some();
+    assert!(visible());
+
+    env::set_caller(bob());
+    assert!(!visible());
+    env::set_caller(alice());
+    grant_access("p1".to_string(), bob()).unwrap();
+    env::set_caller(bob());
+    assert!(visible());
+    env::set_caller(admin());
+    assert!(!visible());
+
+    env::set_caller(alice());
+    revoke_access("p1".to_string(), bob()).unwrap();
+    env::set_caller(bob());
+    assert!(!visible());
+}
+
+// One image uploaded under two IDs is stored once, and survives until the last property using it is gone
+#[test]
+fn shared_images_are_reference_counted() {
+    setup();
+    upload_as_caller("p1", png(1)).unwrap();
+    let hash = upload_as_caller("p2", png(1)).unwrap().hashes.remove(0);
+    let refs = || DECENTRALIZED_PLATFORM.with(|platform| platform.borrow().images.get(&hash).map(|(_, refs)| *refs));
+    assert_eq!(refs(), Some(2));
+
+    delete_property("p1".to_string()).unwrap();
+    purge_property("p1".to_string()).unwrap();
+    assert_eq!(refs(), Some(1));
+    assert_eq!(get_image("p2".to_string()).unwrap().image_data, png(1));
+
+    delete_property("p2".to_string()).unwrap();
+    purge_property("p2".to_string()).unwrap();
+    assert_eq!(refs(), None);
+}
+
+// A repeated image is stored and reported by default, and refused with `DuplicateImage` once blocking is on
+#[test]
+fn duplicate_images_can_be_blocked() {
+    setup();
+    upload_as_caller("p1", png(1)).unwrap();
+    let allowed = upload_as_caller("p2", png(1)).unwrap();
+    assert_eq!(allowed.duplicate_of, ["p1"]);
+
+    env::set_caller(admin());
+    set_block_duplicate_images(true);
+    env::set_caller(alice());
+    let refused = upload_as_caller("p3", png(1));
+    assert!(matches!(refused, Err(PlatformError::DuplicateImage { ref existing_id }) if existing_id == "p1"));
+    assert!(stored("p3").is_none());
+    upload_as_caller("p4", png(2)).unwrap();
+}
+
+// A type with its own image limit is held to it and told that limit, while other types use the default
+#[test]
+fn image_limits_can_differ_by_type() {
+    setup();
+    env::set_caller(admin());
+    set_type_image_limit(PropertyType::Car, Some(2_000));
+
+    env::set_caller(alice());
+    let refused = upload_of_type("car", PropertyType::Car, png_of_len(3_000));
+    assert!(matches!(refused, Err(PlatformError::TooLarge { limit_bytes: 2_000 })));
+    upload_of_type("small-car", PropertyType::Car, png_of_len(1_000)).unwrap();
+    upload_of_type("house", PropertyType::RealEstate, png_of_len(3_000)).unwrap();
+    assert_eq!(get_image_limit(PropertyType::Car), 2_000);
+    assert_eq!(get_image_limit(PropertyType::RealEstate), MAX_IMAGE_BYTES);
+}
+
+// The same bytes hash differently under two salts, and `verify_image` uses the deployment's salt
+#[test]
+fn hash_salts_make_hashes_deployment_specific() {
+    let image = png(1);
+    let unsalted = hash_image(&image, HashAlgo::Sha256, &[]);
+    let salted_a = hash_image(&image, HashAlgo::Sha256, b"deployment-a");
+    let salted_b = hash_image(&image, HashAlgo::Sha256, b"deployment-b");
+    assert_ne!(salted_a, salted_b);
+    assert_ne!(salted_a, unsalted);
+
+    setup();
+    env::set_caller(admin());
+    set_hash_salt(Some(b"deployment-a".to_vec()));
+    env::set_caller(alice());
+    let result = upload_as_caller("p1", image.clone()).unwrap();
+    assert_eq!(result.hashes, [salted_a]);
+    assert!(verify_image("p1".to_string(), image));
+}
+
+// Only the admin can feature a listing, and featured ones are listed and can be sorted first
+#[test]
+fn featured_listings_are_admin_only_and_sort_first() {
+    setup();
+    upload_as_caller("p1", png(1)).unwrap();
+    upload_as_caller("p2", png(2)).unwrap();
+    assert!(std::panic::catch_unwind(|| set_featured("p2".to_string(), true)).is_err());
+    assert!(!property("p2").featured);
+
+    env::set_caller(admin());
+    set_featured("p2".to_string(), true).unwrap();
+    let featured: Vec<String> = get_featured().into_iter().map(|property| property.id).collect();
+    assert_eq!(featured, ["p2"]);
+    let ids = |featured_first| -> Vec<String> {
+        let page = get_properties_sorted(SortKey::Id, false, 0, 10, featured_first);
+        page.entries.into_iter().map(|property| property.id).collect()
+    };
+    assert_eq!(ids(None), ["p1", "p2"]);
+    assert_eq!(ids(Some(true)), ["p2", "p1"]);
+}
+
+// A page of properties with very large descriptions is cut short and flagged instead of overflowing
+#[test]
+fn oversized_pages_are_truncated() {
+    setup();
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let mut platform = platform.borrow_mut();
+        for index in 0..20 {
+            let id = format!("p{:02}", index);
+            let property = Property {
+                id: id.clone(),
+                description: "x".repeat(100_000),
+                ..Default::default()
+            };
+            platform.properties.insert(id, property);
+        }
+    });
+
+    let page = get_properties_paged(0, 20);
+    assert!(page.truncated);
+    assert!(!page.entries.is_empty() && page.entries.len() < 20);
+    let size: usize = page.entries.iter().map(response_size_estimate).sum();
+    assert!(size <= MAX_RESPONSE_BYTES);
+    assert_eq!(page.total, 20);
+
+    let small = get_properties_paged(0, 2);
+    assert!(!small.truncated);
+    assert_eq!(small.entries.len(), 2);
+}
+
+// Once the seller allow-list is on, only listed principals can upload
+#[test]
+fn the_seller_allowlist_gates_uploads() {
+    setup();
+    upload_as_caller("p1", png(1)).unwrap();
+
+    env::set_caller(admin());
+    add_seller(bob());
+    assert!(is_seller(bob()));
+    assert!(!is_seller(alice()));
+
+    env::set_caller(alice());
+    assert!(matches!(upload_as_caller("p2", png(2)), Err(PlatformError::Unauthorized)));
+    env::set_caller(bob());
+    upload_as_caller("p3", png(3)).unwrap();
+
+    env::set_caller(admin());
+    set_seller_allowlist_enabled(false);
+    env::set_caller(alice());
+    upload_as_caller("p2", png(2)).unwrap();
+}
+
+// An owner at the quota can't upload more or be handed another property
+#[test]
+fn owners_are_held_to_the_property_quota() {
+    setup();
+    env::set_caller(admin());
+    set_max_properties_per_owner(Some(2));
+
+    env::set_caller(alice());
+    upload_as_caller("p1", png(1)).unwrap();
+    upload_as_caller("p2", png(2)).unwrap();
+    assert!(matches!(upload_as_caller("p3", png(3)), Err(PlatformError::QuotaExceeded { limit: 2 })));
+
+    env::set_caller(bob());
+    upload_as_caller("b1", png(4)).unwrap();
+    let refused = transfer_property("b1".to_string(), alice(), None);
+    assert!(matches!(refused, Err(PlatformError::QuotaExceeded { limit: 2 })));
+    assert_eq!(property("b1").owner, bob());
+}
+
+// Listings come out in the same order however the properties were inserted
+#[test]
+fn listing_order_ignores_insertion_order() {
+    let listings = |order: &[&str]| {
+        env::set_caller(alice());
+        for (seed, id) in order.iter().enumerate() {
+            upload_as_caller(id, png(seed as u8)).unwrap();
+        }
+        let all = get_properties();
+        let by_owner: Vec<String> = get_properties_by_owner(alice(), None).into_iter().map(|p| p.id).collect();
+        let by_type: Vec<String> = get_properties_by_type(PropertyType::Art, None).into_iter().map(|p| p.id).collect();
+        env::set_caller(admin());
+        reset_platform();
+        (all.into_iter().map(|(id, _, _)| id).collect::<Vec<_>>(), by_owner, by_type)
+    };
+
+    setup();
+    let first = listings(&["b", "c", "a"]);
+    let second = listings(&["c", "a", "b"]);
+    assert_eq!(first.0, ["a", "b", "c"]);
+    assert_eq!(first, second);
+}
+
+// An encrypted upload is served back as the same ciphertext with its key reference, and plaintext isn't
+#[test]
+fn encrypted_images_keep_their_metadata() {
+    setup();
+    let ciphertext = vec![0x13, 0x37, 0x00, 0xff];
+    let options = UploadOptions {
+        encrypted: Some(true),
+        key_id: Some("vetkey-1".to_string()),
+        ..Default::default()
+    };
+    let images = vec![ciphertext.clone()];
+    upload_property("secret".to_string(), PropertyType::Art, images, String::new(), 1, None, Some(options)).unwrap();
+    upload_as_caller("plain", png(1)).unwrap();
+
+    let secret = get_image("secret".to_string()).unwrap();
+    assert_eq!(secret.image_data, ciphertext);
+    assert!(secret.encrypted);
+    assert_eq!(secret.key_id.as_deref(), Some("vetkey-1"));
+    assert!(verify_image("secret".to_string(), ciphertext));
+
+    let plain = get_image("plain".to_string()).unwrap();
+    assert!(!plain.encrypted);
+    assert_eq!(plain.key_id, None);
+}
+
+// An async endpoint that copies state out, yields, and then mutates leaves the state free while it waits
+#[test]
+fn state_borrows_are_not_held_across_awaits() {
+    use std::future::Future;
+    use std::task::{Context, Poll, Waker};
+
+    // Stands in for an inter-canister call: pending on the first poll, ready on the next
+    struct YieldOnce(bool);
+    impl Future for YieldOnce {
+        type Output = ();
+        fn poll(mut self: std::pin::Pin<&mut Self>, _: &mut Context<'_>) -> Poll<()> {
+            if std::mem::replace(&mut self.0, true) {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        }
+    }
+
+    setup();
+    upload_as_caller("p1", png(1)).unwrap();
+    let mut endpoint = Box::pin(async {
+        let price = with_state(|platform| platform.properties.get("p1").unwrap().price_e8s);
+        YieldOnce(false).await;
+        with_state_mut(|platform| {
+            let mut property = platform.properties.get_mut("p1").unwrap();
+            property.price_e8s = price * 2;
+            property.commit();
+        });
+    });
+    let mut context = Context::from_waker(Waker::noop());
+    assert!(endpoint.as_mut().poll(&mut context).is_pending());
+
+    // Another message runs while the first is waiting
+    update_description("p1".to_string(), "Edited meanwhile".to_string()).unwrap();
+    assert!(endpoint.as_mut().poll(&mut context).is_ready());
+    assert_eq!(property("p1").price_e8s, 200);
+    assert_eq!(property("p1").description, "Edited meanwhile");
+}
+
+// Hash arguments of the wrong length or with non-hex characters are refused, and valid ones lowercased
+#[test]
+fn hash_arguments_are_validated() {
+    setup();
+    let hash = upload_as_caller("p1", png(1)).unwrap().hashes.remove(0);
+
+    assert!(matches!(parse_hash("abc123"), Err(PlatformError::InvalidInput(_))));
+    assert!(matches!(parse_hash(&"g".repeat(64)), Err(PlatformError::InvalidInput(_))));
+    assert_eq!(parse_hash(&format!(" {} ", hash.to_uppercase())).unwrap(), hash);
+
+    assert!(matches!(get_properties_by_hash("zz".to_string()), Err(PlatformError::InvalidInput(_))));
+    let found = get_properties_by_hash(hash.to_uppercase()).unwrap();
+    assert_eq!(found.into_iter().map(|property| property.id).collect::<Vec<_>>(), ["p1"]);
+}
+
+// A blocked word anywhere in the description, in any case, refuses the upload or edit; parts of words don't
+#[test]
+fn blocked_keywords_reject_listings() {
+    setup();
+    env::set_caller(admin());
+    add_blocked_keyword("scam".to_string());
+
+    env::set_caller(alice());
+    let upload_with = |property_id: &str, description: &str| {
+        let description = description.to_string();
+        upload_property(property_id.to_string(), PropertyType::Art, vec![png(1)], description, 1, None, None)
+    };
+    assert!(matches!(upload_with("p1", "Totally not a SCAM!"), Err(PlatformError::InvalidInput(_))));
+    upload_with("p2", "Scampi recipe book").unwrap();
+    let edit = update_description("p2".to_string(), "a scam".to_string());
+    assert!(matches!(edit, Err(PlatformError::InvalidInput(_))));
+
+    env::set_caller(admin());
+    remove_blocked_keyword("scam".to_string());
+    env::set_caller(alice());
+    upload_with("p1", "Totally not a SCAM!").unwrap();
+}
+
+// Retrying an auto-ID upload with the same idempotency key returns the first result without a second record
+#[test]
+fn idempotent_uploads_run_once() {
+    setup();
+    let upload_with_key = |key: &str, seed: u8| {
+        let options = UploadOptions {
+            idempotency_key: Some(key.to_string()),
+            ..Default::default()
+        };
+        upload_property_auto_id(PropertyType::Art, vec![png(seed)], String::new(), 1, None, Some(options))
+    };
+
+    let first = upload_with_key("retry-1", 1).unwrap();
+    let retry = upload_with_key("retry-1", 1).unwrap();
+    assert_eq!(first, retry);
+    assert_eq!(property_count(), 1);
+
+    let other = upload_with_key("retry-2", 2).unwrap();
+    assert_ne!(other, first);
+    assert_eq!(property_count(), 2);
+}
+
+// Each recorded view bumps the counter, which the view-count query reports
+#[test]
+fn views_are_counted() {
+    setup();
+    upload_as_caller("p1", png(1)).unwrap();
+    assert_eq!(get_view_count("p1".to_string()), Some(0));
+
+    env::set_caller(bob());
+    assert_eq!(record_view("p1".to_string()).unwrap(), 1);
+    assert_eq!(record_view("p1".to_string()).unwrap(), 2);
+    assert_eq!(get_view_count("p1".to_string()), Some(2));
+    assert!(matches!(record_view("p2".to_string()), Err(PlatformError::NotFound)));
+    assert_eq!(get_view_count("p2".to_string()), None);
+}
+
+// `transfer_all` moves every property, recording the new owner in each provenance, and respects quotas
+#[test]
+fn transfer_all_moves_every_property() {
+    setup();
+    upload_as_caller("p1", png(1)).unwrap();
+    upload_as_caller("p2", png(2)).unwrap();
+    upload_as_caller("p3", png(3)).unwrap();
+
+    env::set_caller(admin());
+    set_max_properties_per_owner(Some(2));
+    env::set_caller(alice());
+    assert!(matches!(transfer_all(bob()), Err(PlatformError::QuotaExceeded { limit: 2 })));
+    assert_eq!(get_my_properties().len(), 3);
+
+    env::set_caller(admin());
+    set_max_properties_per_owner(None);
+    env::set_caller(alice());
+    assert_eq!(transfer_all(bob()).unwrap(), 3);
+    assert!(get_my_properties().is_empty());
+    assert_eq!(get_properties_by_owner(bob(), None).len(), 3);
+    let last_owner = get_provenance("p2".to_string()).last().map(|record| record.owner);
+    assert_eq!(last_owner, Some(bob()));
+}
+
+// Uploads of a type with required attributes are refused until every required key is given
+#[test]
+fn required_attributes_are_enforced() {
+    setup();
+    env::set_caller(admin());
+    set_required_attributes(PropertyType::Car, vec!["vin".to_string()]);
+    assert_eq!(get_required_attributes(), [("Car".to_string(), vec!["vin".to_string()])]);
+
+    env::set_caller(alice());
+    let upload_with = |attributes: Vec<(String, String)>| {
+        let options = UploadOptions {
+            attributes: Some(attributes),
+            ..Default::default()
+        };
+        upload_property("car".to_string(), PropertyType::Car, vec![png(1)], String::new(), 1, None, Some(options))
+    };
+    let refused = upload_with(vec![("color".to_string(), "red".to_string())]);
+    assert!(matches!(refused, Err(PlatformError::MissingAttribute { ref key }) if key == "vin"));
+    upload_with(vec![("vin".to_string(), "1HGCM82633A004352".to_string())]).unwrap();
+    upload_of_type("house", PropertyType::RealEstate, png(2)).unwrap();
+}
+
+// A deleted property shows in its owner's trash, only the owner can restore it, and it then lists again
+#[test]
+fn owners_can_list_and_restore_their_trash() {
+    setup();
+    upload_as_caller("p1", png(1)).unwrap();
+    delete_property("p1".to_string()).unwrap();
+    assert!(get_my_properties().is_empty());
+    let trashed: Vec<String> = get_my_trash().into_iter().map(|property| property.id).collect();
+    assert_eq!(trashed, ["p1"]);
+
+    env::set_caller(bob());
+    assert!(get_my_trash().is_empty());
+    assert!(matches!(restore_property("p1".to_string()), Err(PlatformError::Unauthorized)));
+    env::set_caller(admin());
+    assert_eq!(get_all_trash().len(), 1);
+
+    env::set_caller(alice());
+    restore_property("p1".to_string()).unwrap();
+    assert!(get_my_trash().is_empty());
+    let listed: Vec<String> = get_my_properties().into_iter().map(|property| property.id).collect();
+    assert_eq!(listed, ["p1"]);
+}
+
+// Storing the same bytes under a hash again adds a reference, and different bytes under it are refused
+#[test]
+fn the_image_store_guards_against_hash_collisions() {
+    let mut platform = DecentralizedPlatform::default();
+    let hash = hash_image(&png(1), HashAlgo::Sha256, &[]);
+    assert_eq!(hash.len(), 64);
+
+    platform.retain_image(hash.clone(), png(1)).unwrap();
+    platform.retain_image(hash.clone(), png(1)).unwrap();
+    assert_eq!(platform.images[&hash].1, 2);
+
+    let collision = platform.retain_image(hash.clone(), png(2));
+    assert!(matches!(collision, Err(PlatformError::HashCollision { hash: ref collided }) if *collided == hash));
+    assert_eq!(platform.images[&hash], (png(1), 2));
+}
+
+// Price and bid math near u64::MAX errors or stays exact instead of wrapping
+#[test]
+fn marketplace_math_does_not_wrap() {
+    let (royalty, seller) = royalty_split(u64::MAX, MAX_ROYALTY_BPS).unwrap();
+    assert_eq!(royalty.checked_add(seller), Some(u64::MAX));
+    assert!(royalty > 0 && seller > royalty);
+
+    setup();
+    upload_property("p1".to_string(), PropertyType::Art, vec![png(1)], String::new(), u64::MAX, None, None).unwrap();
+    let in_range = get_properties_in_price_range(u64::MAX - 1, u64::MAX, 0, 10);
+    assert_eq!(in_range.total, 1);
+    start_auction("p1".to_string(), 1, env::time() + 1_000).unwrap();
+
+    env::set_caller(bob());
+    place_bid("p1".to_string(), u64::MAX).unwrap();
+    env::set_caller(admin());
+    assert!(matches!(place_bid("p1".to_string(), u64::MAX), Err(PlatformError::Overflow)));
+}
+
+// Once the platform holds its cap, uploads fail with `PlatformFull`, and a batch stops cleanly at the cap
+#[test]
+fn the_platform_cap_limits_uploads() {
+    setup();
+    env::set_caller(admin());
+    set_max_total_properties(Some(2));
+
+    env::set_caller(alice());
+    upload_as_caller("p1", png(1)).unwrap();
+    let item = |property_id: &str, seed: u8| UploadRequest {
+        property_id: property_id.to_string(),
+        property_type: PropertyType::Art,
+        images: vec![png(seed)],
+        description: String::new(),
+        price_e8s: 1,
+        hash_algo: None,
+        options: None,
+    };
+    let results = upload_properties_batch(vec![item("p2", 2), item("p3", 3)]).unwrap();
+    assert!(results[0].is_ok());
+    assert!(matches!(results[1], Err(PlatformError::PlatformFull { limit: 2 })));
+
+    env::set_caller(bob());
+    assert!(matches!(upload_as_caller("p4", png(4)), Err(PlatformError::PlatformFull { limit: 2 })));
+    assert_eq!(property_count(), 2);
+}
+
+// A lapsed hold found by an endpoint that then refuses the call stays stored until a sweep logs it;
+// one released on the way to a successful call is logged as expired
+#[test]
+fn lapsed_holds_are_only_released_with_an_event() {
+    setup();
+    let options = UploadOptions {
+        expires_at: Some(env::time() + RESERVATION_TTL_NANOS * 2),
+        ..Default::default()
+    };
+    upload_property("p1".to_string(), PropertyType::Art, vec![png(1)], String::new(), 1, None, Some(options)).unwrap();
+    upload_as_caller("p2", png(2)).unwrap();
+    env::set_caller(bob());
+    reserve_property("p1".to_string()).unwrap();
+    reserve_property("p2".to_string()).unwrap();
+    env::advance_time(RESERVATION_TTL_NANOS * 2);
+
+    env::set_caller(alice());
+    let before = stored("p1");
+    assert!(matches!(start_auction("p1".to_string(), 1, env::time() + 1_000), Err(PlatformError::NotAvailable)));
+    assert_eq!(stored("p1"), before);
+
+    start_auction("p2".to_string(), 1, env::time() + 1_000).unwrap();
+    let kinds: Vec<AuditEventKind> = get_events(0, 100)
+        .into_iter()
+        .filter(|event| event.property_id == "p2")
+        .map(|event| event.kind)
+        .collect();
+    assert!(matches!(
+        kinds.as_slice(),
+        [.., AuditEventKind::ReservationExpired, AuditEventKind::AuctionStarted { .. }]
+    ));
+}
+
+// An ended auction that nobody can win closes unsold: a bidder at their property quota is passed over without
+// being charged, and the listing is available again
+#[test]
+fn auctions_pass_over_bidders_who_cannot_take_the_property() {
+    use std::future::Future;
+    use std::task::{Context, Waker};
+
+    setup();
+    upload_as_caller("p1", png(1)).unwrap();
+    start_auction("p1".to_string(), 10, env::time() + 1_000).unwrap();
+    env::set_caller(bob());
+    upload_as_caller("b1", png(2)).unwrap();
+    place_bid("p1".to_string(), 50).unwrap();
+    env::set_caller(admin());
+    set_max_properties_per_owner(Some(1));
+    env::advance_time(1_000);
+
+    // Nobody is left to pay, so settling finishes without waiting on the ledger
+    let mut settlement = Box::pin(settle_auction("p1".to_string()));
+    let settled = settlement.as_mut().poll(&mut Context::from_waker(Waker::noop()));
+    assert!(matches!(settled, std::task::Poll::Ready(Ok(()))));
+    let listing = property("p1");
+    assert_eq!(listing.owner, alice());
+    assert_eq!(listing.status, ListingStatus::Available);
+    assert!(listing.auction.is_none());
+    let last = get_events(0, 100).pop().unwrap();
+    assert!(matches!(last.kind, AuditEventKind::AuctionSettled { winner: None }));
+}
+
+// Every listing query shows the same lazily applied state: an expired listing drops off for everyone but its
+// owner, who sees it as expired, and a lapsed hold reads as available
+#[test]
+fn listing_queries_agree_on_expiry_and_lapsed_holds() {
+    setup();
+    let options = UploadOptions {
+        expires_at: Some(env::time() + 1_000),
+        tags: Some(vec!["vintage".to_string()]),
+        ..Default::default()
+    };
+    upload_property("p1".to_string(), PropertyType::Art, vec![png(1)], "Old".to_string(), 1, None, Some(options))
+        .unwrap();
+    upload_as_caller("p2", png(2)).unwrap();
+    env::set_caller(bob());
+    add_favorite("p1".to_string()).unwrap();
+    reserve_property("p2".to_string()).unwrap();
+    env::advance_time(RESERVATION_TTL_NANOS);
+
+    let ids = |properties: Vec<Property>| -> Vec<String> { properties.into_iter().map(|p| p.id).collect() };
+    assert_eq!(ids(get_properties_by_type(PropertyType::Art, None)), ["p2"]);
+    assert_eq!(ids(get_properties_by_owner(alice(), None)), ["p2"]);
+    assert!(get_properties_by_tag("vintage".to_string(), None).is_empty());
+    assert!(get_favorites().is_empty());
+    assert!(search_properties("old".to_string(), None).is_empty());
+    let hash = property("p1").image_hashes[0].clone();
+    assert!(get_properties_by_hash(hash).unwrap().is_empty());
+    let p2 = get_properties_by_type(PropertyType::Art, None).remove(0);
+    assert_eq!(p2.status, ListingStatus::Available);
+    assert!(p2.reservation.is_none());
+
+    env::set_caller(alice());
+    let mine = get_my_properties();
+    assert_eq!(ids(mine.clone()), ["p1", "p2"]);
+    assert_eq!(mine[0].status, ListingStatus::Expired);
+}
+
+// An upgrade keeps the properties in the stable map and restores the rest of the state `pre_upgrade` saved,
+// along with the admin
+#[test]
+fn upgrades_restore_the_saved_state() {
+    setup();
+    upload_as_caller("p1", png(1)).unwrap();
+    add_favorite("p1".to_string()).unwrap();
+    env::set_caller(admin());
+    set_max_total_properties(Some(10));
+    let before = stored("p1");
+    let events = get_events(0, 100).len();
+
+    pre_upgrade();
+    // A new module starts with empty heap state
+    DECENTRALIZED_PLATFORM.with(|platform| *platform.borrow_mut() = DecentralizedPlatform::default());
+    ADMIN.with(|admin| *admin.borrow_mut() = None);
+    env::set_caller(bob());
+    post_upgrade(None);
+
+    assert_eq!(stored("p1"), before);
+    assert_eq!(get_admin(), Some(admin()));
+    assert_eq!(get_events(0, 100).len(), events);
+    DECENTRALIZED_PLATFORM.with(|platform| {
+        let platform = platform.borrow();
+        assert_eq!(platform.max_total_properties, Some(10));
+        assert!(platform.favorites[&alice()].contains("p1"));
+    });
+    assert_eq!(get_properties_by_owner(alice(), None).len(), 1);
+}
+
+// A swap is held to the transfer cooldown, so a property just handed over can't be swapped on until it ends
+#[test]
+fn swaps_wait_out_the_transfer_cooldown() {
+    setup();
+    env::set_caller(admin());
+    set_transfer_cooldown(Some(60));
+    env::set_caller(alice());
+    upload_as_caller("a1", png(1)).unwrap();
+    upload_as_caller("a2", png(2)).unwrap();
+    transfer_property("a1".to_string(), bob(), None).unwrap();
+
+    env::set_caller(bob());
+    approve_swap("a1".to_string(), "a2".to_string()).unwrap();
+    env::set_caller(alice());
+    approve_swap("a2".to_string(), "a1".to_string()).unwrap();
+    env::set_caller(bob());
+    let early = swap_properties("a1".to_string(), "a2".to_string(), alice());
+    assert!(matches!(early, Err(PlatformError::CooldownActive { retry_after_secs: 60 })));
+    assert_eq!(property("a1").owner, bob());
+
+    env::advance_time(60_000_000_000);
+    swap_properties("a1".to_string(), "a2".to_string(), alice()).unwrap();
+    assert_eq!(property("a1").owner, alice());
+    assert_eq!(property("a2").owner, bob());
+}
+
+// Owner counts follow uploads, transfers, and deletions
+#[test]
+fn owners_are_counted_from_the_index() {
+    setup();
+    upload_as_caller("p1", png(1)).unwrap();
+    upload_as_caller("p2", png(2)).unwrap();
+    upload_as_caller("p3", png(3)).unwrap();
+    transfer_property("p3".to_string(), bob(), None).unwrap();
+    assert_eq!(list_owners(), [(alice(), 2), (bob(), 1)]);
+
+    delete_property("p1".to_string()).unwrap();
+    delete_property("p2".to_string()).unwrap();
+    assert_eq!(list_owners(), [(bob(), 1)]);
+}
+
+// Every audit event is sent to each subscriber's callback method as a one-way call
+#[test]
+fn subscribers_are_notified_of_events() {
+    setup();
+    let subscriber = Principal::from_slice(&[7; 10]);
+    env::set_caller(admin());
+    subscribe(subscriber, "on_event".to_string());
+    env::set_caller(alice());
+    upload_as_caller("p1", png(1)).unwrap();
+
+    let sent = env::notifications();
+    let (canister, method, args) = sent.last().unwrap();
+    assert_eq!((*canister, method.as_str()), (subscriber, "on_event"));
+    let event: AuditEvent = candid::decode_one(args).unwrap();
+    assert!(matches!(event.kind, AuditEventKind::Uploaded));
+    assert_eq!(event.property_id, "p1");
+}
@@ -1,3 +1,3 @@
+mod env;
 mod hashing;
-
-
+mod merkle;
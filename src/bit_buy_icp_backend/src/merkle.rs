@@ -0,0 +1,64 @@
+// Plain binary Merkle tree over SHA-256, used to publish one commitment to every listed property.
+// Sibling pairs are hashed in sorted order, so a proof is just the list of siblings from leaf to root.
+// A node without a sibling is carried up to the next level unchanged.
+
+use ic_certified_map::Hash;
+use sha2::{Digest, Sha256};
+
+// Root of the tree built from an empty set of leaves
+pub const EMPTY_ROOT: Hash = [0; 32];
+
+// Leaf for one property: its ID (length-prefixed so it can't run into the hash) and its image hash
+pub fn leaf_hash(property_id: &str, image_hash: &str) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([0x00]);
+    hasher.update((property_id.len() as u32).to_be_bytes());
+    hasher.update(property_id.as_bytes());
+    hasher.update(image_hash.as_bytes());
+    hasher.finalize().into()
+}
+
+fn node_hash(a: &Hash, b: &Hash) -> Hash {
+    let (left, right) = if a <= b { (a, b) } else { (b, a) };
+    let mut hasher = Sha256::new();
+    hasher.update([0x01]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+fn next_level(level: &[Hash]) -> Vec<Hash> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [left, right] => node_hash(left, right),
+            [single] => *single,
+            _ => unreachable!("chunks(2) yields one or two nodes"),
+        })
+        .collect()
+}
+
+pub fn root(leaves: &[Hash]) -> Hash {
+    if leaves.is_empty() {
+        return EMPTY_ROOT;
+    }
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        level = next_level(&level);
+    }
+    level[0]
+}
+
+// Siblings needed to rebuild the root from the leaf at `index`, ordered from the leaf upwards
+pub fn proof(leaves: &[Hash], mut index: usize) -> Vec<Hash> {
+    let mut siblings = Vec::new();
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        if let Some(sibling) = level.get(index ^ 1) {
+            siblings.push(*sibling);
+        }
+        level = next_level(&level);
+        index /= 2;
+    }
+    siblings
+}